@@ -1,405 +1,382 @@
-use std::collections::HashMap;
-use std::str::FromStr;
+use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::Result;
-use reqwest::Method;
-use serde::de::DeserializeOwned;
-
-use async_trait::async_trait;
+use reqwest::header::HeaderMap;
 use reqwest::Client as AsyncClient;
-
+use reqwest::StatusCode;
+
+use crate::endpoints::{self, Body, Call, FromResponse};
+use crate::error::{GotifyError, Result};
+use crate::ids::*;
+use crate::image::{DownloadedImage, Image};
+use crate::message::NewMessage;
+use crate::paging::{MessagesStream, Pager};
+use crate::priority::Priority;
 use crate::response_types::*;
-use crate::Gotify;
-
-pub struct AsyncGotify<'a> {
-    gotify: Gotify<'a>,
+use crate::retry::{Outcome, RetryPolicy};
+use crate::stream::MessageStream;
+use crate::subscription::{ReconnectPolicy, Subscription};
+use crate::token_file;
+use crate::{Credentials, Gotify};
+
+/// An async Gotify client.
+///
+/// Cloning is cheap: clones share the configuration and the connection pool.
+#[derive(Clone)]
+pub struct AsyncGotify {
+    gotify: Arc<Gotify>,
     client: AsyncClient,
 }
 
-impl<'a> AsyncGotify<'a> {
+impl AsyncGotify {
     pub fn new(
-        base_url: &'a str,
-        app_token: Option<&'a str>,
-        client_token: Option<&'a str>,
-    ) -> Self {
-        let gotify = Gotify::new(base_url, app_token, client_token);
-        let client = AsyncClient::new();
-        Self { gotify, client }
-    }
-    pub fn from(gotify: Gotify<'a>) -> Self {
-        let client = AsyncClient::new();
-        Self { gotify, client }
-    }
-
-    fn get_token(&self, auth_mode: Option<&str>) -> Option<&'a str> {
-        if let Some(mode) = auth_mode {
-            if mode == "app" {
-                self.gotify.app_token
-            } else {
-                self.gotify.client_token
-            }
-        } else {
-            self.gotify.app_token
+        base_url: &str,
+        app_token: Option<&str>,
+        client_token: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self::from(Gotify::new(base_url, app_token, client_token)?))
+    }
+    pub fn from(gotify: Gotify) -> Self {
+        Self::with_client(gotify, AsyncClient::new())
+    }
+    /// Uses a preconfigured `reqwest::Client`, e.g. for proxies, timeouts or TLS settings.
+    pub fn with_client(gotify: Gotify, client: AsyncClient) -> Self {
+        Self {
+            gotify: Arc::new(gotify),
+            client,
         }
     }
-}
-
-#[async_trait]
-trait AsyncGotifyImpl {
-    async fn do_request<T: DeserializeOwned>(
-        &self,
-        method: &str,
-        endpoint_url: &str,
-        data: Option<HashMap<String, Option<String>>>,
-        file: Option<tokio::fs::File>,
-        auth_mode: Option<&str>,
-    ) -> Result<T>;
-
-    async fn applications(&self) -> Result<Vec<Application>>;
-
-    async fn create_application(&self, name: String, description: String) -> Result<Application>;
-
-    async fn update_application(
-        &self,
-        id: i32,
-        name: String,
-        description: Option<String>,
-    ) -> Result<Application>;
-
-    async fn delete_application(&self, id: i32) -> Result<()>;
-
-    async fn upload_application_image(
-        &self,
-        id: i32,
-        image: tokio::fs::File,
-    ) -> Result<Application>;
-
-    async fn get_messages(
-        &self,
-        app_id: Option<i32>,
-        limit: Option<i32>,
-        since: Option<i32>,
-    ) -> Result<PagedMessages>;
-
-    async fn create_message(
-        &self,
-        message: String,
-        priority: Option<i32>,
-        title: Option<String>,
-        // TODO: extras
-    ) -> Result<Message>;
-
-    async fn delete_messages(&self, app_id: Option<i32>) -> Result<()>;
-
-    async fn delete_message(&self, msg_id: i32) -> Result<()>;
-
-    async fn get_clients(&self) -> Result<Vec<Client>>;
 
-    async fn create_client(&self, name: String) -> Result<Client>;
-
-    async fn update_client(&self, id: i32, name: String) -> Result<Client>;
-
-    async fn delete_client(&self, id: i32) -> Result<()>;
-
-    async fn get_current_user(&self) -> Result<User>;
-
-    async fn set_password(&self, passwd: String) -> Result<()>;
-
-    async fn get_users(&self) -> Result<Vec<User>>;
-
-    async fn create_user(&self, name: String, passwd: String, admin: Option<bool>) -> Result<User>;
-
-    async fn get_user(&self, id: i32) -> Result<User>;
-
-    async fn update_user(
-        &self,
-        id: i32,
-        name: Option<String>,
-        passwd: Option<String>,
-        admin: Option<bool>,
-    ) -> Result<User>;
-
-    async fn delete_user(&self, id: i32) -> Result<()>;
-
-    async fn get_health(&self) -> Result<Health>;
-
-    async fn get_plugins(&self) -> Result<Vec<PluginConf>>;
-
-    async fn get_plugin_config(&self, id: i32) -> Result<PluginConf>;
+    pub(crate) fn app_token(&self) -> Option<&str> {
+        self.gotify.app_token.as_deref()
+    }
 
-    /// TODO(ethanhs): Figure out what this looks like
-    /* async fn update_plugin_config(&self, id: i32); */
+    /// Returns a client that sends `token` as the app token, sharing this client's connection pool.
+    pub fn with_app_token(&self, token: impl Into<String>) -> Self {
+        self.with_config(|gotify| gotify.app_token = Some(token.into()))
+    }
 
-    async fn disable_plugin(&self, id: i32) -> Result<()>;
+    /// Returns a client that sends `token` as the client token, sharing this client's connection pool.
+    pub fn with_client_token(&self, token: impl Into<String>) -> Self {
+        self.with_config(|gotify| gotify.client_token = Some(token.into()))
+    }
 
-    async fn get_plugin_display(&self, id: i32) -> Result<String>;
+    /// Returns a client that authenticates client-token endpoints with basic auth instead,
+    /// sharing this client's connection pool.
+    pub fn with_basic_auth(&self, user: impl Into<String>, pass: impl Into<String>) -> Self {
+        self.with_config(|gotify| {
+            gotify.client_token = None;
+            gotify.basic_auth = Some((user.into(), pass.into()));
+        })
+    }
 
-    async fn enable_plugin(&self, id: i32) -> Result<()>;
+    /// Returns a client that retries failed requests as `policy` says, sharing this client's
+    /// connection pool.
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        self.with_config(|gotify| gotify.retry = policy)
+    }
 
-    async fn get_version(&self) -> Result<VersionInfo>;
-}
+    fn with_config(&self, update: impl FnOnce(&mut Gotify)) -> Self {
+        let mut gotify = (*self.gotify).clone();
+        update(&mut gotify);
+        Self {
+            gotify: Arc::new(gotify),
+            client: self.client.clone(),
+        }
+    }
 
-#[async_trait]
-impl<'a> AsyncGotifyImpl for AsyncGotify<'a> {
-    async fn do_request<T: DeserializeOwned>(
-        &self,
-        method: &str,
-        endpoint_url: &str,
-        data: Option<HashMap<String, Option<String>>>,
-        file: Option<tokio::fs::File>,
-        auth_mode: Option<&str>,
-    ) -> Result<T> {
-        let method = Method::from_str(&method)?;
-        let request_url = format!("{}/{}", self.gotify.base_url, endpoint_url);
-        let mut request = self.client.request(method.clone(), request_url);
-        if let Some(f) = file {
-            request = request.body(f);
-        } else {
-            if let Some(data) = data {
-                match method {
-                    Method::GET => {
-                        request = request.query(&data);
-                    }
-                    _ => {
-                        request = request.json(&data);
-                    }
+    async fn execute<T: FromResponse>(&self, call: Call<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            let response = self.send(&call).await;
+            let outcome = match &response {
+                Ok((status, _, _)) if status.is_success() => None,
+                Ok((status, headers, _)) => Some(Outcome::Status(*status, headers)),
+                Err(GotifyError::Transport(_)) => Some(Outcome::Transport),
+                Err(_) => None,
+            };
+            let delay = outcome.and_then(|outcome| {
+                self.gotify
+                    .retry
+                    .delay(&call.method, call.sends_message, attempt, outcome)
+            });
+            match delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => {
+                    let (status, headers, body) = response?;
+                    return T::from_response(status, &headers, body);
                 }
-                request = request.json(&data);
             }
+            attempt += 1;
+        }
+    }
+
+    /// Sends one attempt of `call`; the call is kept so it can be sent again.
+    async fn send<T>(&self, call: &Call<T>) -> Result<(StatusCode, HeaderMap, Vec<u8>)> {
+        let request_url = self.gotify.url(&call.path)?;
+        let mut request = self.client.request(call.method.clone(), request_url);
+        if let Some(query) = &call.query {
+            request = request.query(query);
+        }
+        match &call.body {
+            Body::Empty => {}
+            Body::Json(data) => request = request.json(data),
+            Body::Image { field, image } => {
+                let (bytes, file_name, mime_type) = image.clone().into_parts();
+                let part = reqwest::multipart::Part::bytes(bytes)
+                    .file_name(file_name)
+                    .mime_str(mime_type)?;
+                request = request.multipart(reqwest::multipart::Form::new().part(*field, part));
+            }
+        }
+        match self.gotify.credentials(&call.auth)? {
+            Credentials::Token(token) => request = request.header("X-Gotify-Key", token),
+            Credentials::Basic(user, pass) => request = request.basic_auth(user, Some(pass)),
+            Credentials::None => {}
         }
-        let token = self.get_token(auth_mode).expect("missing token");
-        request = request.header("X-Gotify-Key", token);
         let response = request.send().await?;
-        Ok(response.json::<T>().await?)
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes().await?;
+        Ok((status, headers, body.to_vec()))
     }
 
-    async fn applications(&self) -> Result<Vec<Application>> {
-        self.do_request("get", "/applications", None, None, None)
-            .await
+    pub async fn applications(&self) -> Result<Vec<Application>> {
+        self.execute(endpoints::applications()).await
     }
 
-    async fn create_application(&self, name: String, description: String) -> Result<Application> {
-        let mut data = HashMap::new();
-        data.insert("name".to_owned(), Some(name));
-        data.insert("description".to_owned(), Some(description));
-        self.do_request("post", "/application", Some(data), None, None)
+    pub async fn create_application(
+        &self,
+        name: String,
+        description: String,
+    ) -> Result<Application> {
+        self.execute(endpoints::create_application(name, description))
             .await
     }
 
-    async fn update_application(
+    pub async fn update_application(
         &self,
-        id: i32,
+        id: AppId,
         name: String,
         description: Option<String>,
     ) -> Result<Application> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        data.insert("description".to_string(), description);
-        self.do_request("put", &format!("/application/{id}"), Some(data), None, None)
+        self.execute(endpoints::update_application(id, name, description))
             .await
     }
 
-    async fn delete_application(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/application/{id}"), None, None, None)
+    pub async fn delete_application(&self, id: AppId) -> Result<()> {
+        self.execute(endpoints::delete_application(id)).await
+    }
+
+    /// Uploads the application's image, e.g. `Image::from_path_async("icon.png").await?`.
+    pub async fn upload_application_image(&self, id: AppId, image: Image) -> Result<Application> {
+        self.execute(endpoints::upload_application_image(id, image))
             .await
     }
 
-    async fn upload_application_image(
-        &self,
-        id: i32,
-        image: tokio::fs::File,
-    ) -> Result<Application> {
-        self.do_request(
-            "post",
-            &format!("/application/{id}/image"),
-            None,
-            Some(image),
-            None,
-        )
-        .await
+    pub async fn delete_application_image(&self, id: AppId) -> Result<()> {
+        self.execute(endpoints::delete_application_image(id)).await
+    }
+
+    /// Fetches the image at `app.image`, which is relative to the base URL.
+    pub async fn download_application_image(&self, app: &Application) -> Result<DownloadedImage> {
+        self.execute(endpoints::download_application_image(app))
+            .await
     }
 
-    async fn get_messages(
+    pub async fn get_messages(
         &self,
-        app_id: Option<i32>,
+        app_id: Option<AppId>,
         limit: Option<i32>,
-        since: Option<i32>,
+        since: Option<MessageId>,
     ) -> Result<PagedMessages> {
-        let mut data = HashMap::new();
-        data.insert("limit".to_string(), limit.map(|i| i.to_string()));
-        data.insert("since".to_string(), since.map(|i| i.to_string()));
-        if let Some(id) = app_id {
-            self.do_request(
-                "get",
-                &format!("/application/{id}/message"),
-                Some(data),
-                None,
-                None,
-            )
+        self.execute(endpoints::get_messages(app_id, limit, since))
             .await
-        } else {
-            self.do_request("get", "/message", Some(data), None, None)
-                .await
-        }
     }
 
-    async fn create_message(
+    /// Streams all messages, or those of one application, newest first,
+    /// fetching `page_size` messages per request.
+    pub fn messages_stream(&self, app_id: Option<AppId>, page_size: i32) -> MessagesStream {
+        MessagesStream::new(self.clone(), Pager::new(app_id, page_size))
+    }
+
+    /// Opens the `/stream` WebSocket and yields each new message as the server pushes it.
+    pub async fn stream(&self) -> Result<MessageStream> {
+        MessageStream::connect(&self.gotify).await
+    }
+
+    /// Like `stream`, but reconnects after disconnects and backfills any
+    /// messages missed in the meantime.
+    pub fn subscribe(&self, policy: ReconnectPolicy) -> Subscription {
+        Subscription::new(self.clone(), None, policy)
+    }
+
+    /// Like `subscribe`, but first delivers every message newer than `last_id`,
+    /// e.g. the last one handled before a restart.
+    pub fn subscribe_after(&self, last_id: MessageId, policy: ReconnectPolicy) -> Subscription {
+        Subscription::new(self.clone(), Some(last_id), policy)
+    }
+
+    pub async fn create_message(
         &self,
         message: String,
-        priority: Option<i32>,
+        priority: Option<Priority>,
         title: Option<String>,
-        // TODO: extras
     ) -> Result<Message> {
-        let mut data = HashMap::new();
-        data.insert("message".to_string(), Some(message));
-        data.insert("priority".to_string(), priority.map(|i| i.to_string()));
-        data.insert("title".to_string(), title);
-        self.do_request("post", "/message", Some(data), None, Some("app"))
-            .await
+        self.send_message(NewMessage {
+            message,
+            title,
+            priority,
+            extras: Default::default(),
+        })
+        .await
     }
 
-    async fn delete_messages(&self, app_id: Option<i32>) -> Result<()> {
-        if let Some(id) = app_id {
-            self.do_request(
-                "delete",
-                &format!("/application/{id}/message"),
-                None,
-                None,
-                None,
-            )
-            .await
-        } else {
-            self.do_request("delete", &format!("/message"), None, None, None)
-                .await
-        }
+    /// Sends a message built with `MessageBuilder`, including its extras.
+    pub async fn send_message(&self, message: impl Into<NewMessage>) -> Result<Message> {
+        let mut message = message.into();
+        self.gotify.retry.prepare_message(&mut message);
+        self.execute(endpoints::create_message(message)).await
     }
 
-    async fn delete_message(&self, msg_id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/message/{msg_id}"), None, None, None)
-            .await
+    pub async fn delete_messages(&self, app_id: Option<AppId>) -> Result<()> {
+        self.execute(endpoints::delete_messages(app_id)).await
     }
 
-    async fn get_clients(&self) -> Result<Vec<Client>> {
-        self.do_request("get", "/client", None, None, None).await
+    pub async fn delete_message(&self, msg_id: MessageId) -> Result<()> {
+        self.execute(endpoints::delete_message(msg_id)).await
     }
 
-    async fn create_client(&self, name: String) -> Result<Client> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        self.do_request("post", "/client", Some(data), None, None)
-            .await
+    pub async fn get_clients(&self) -> Result<Vec<Client>> {
+        self.execute(endpoints::get_clients()).await
     }
 
-    async fn update_client(&self, id: i32, name: String) -> Result<Client> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        self.do_request("put", &format!("/client/{id}"), Some(data), None, None)
-            .await
+    pub async fn create_client(&self, name: String) -> Result<Client> {
+        self.execute(endpoints::create_client(name)).await
     }
 
-    async fn delete_client(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/client/{id}"), None, None, None)
-            .await
+    /// Creates a client named `client_name` using the user's credentials and
+    /// returns an `AsyncGotify` authenticated with the new client token.
+    pub async fn login(
+        &self,
+        username: &str,
+        password: &str,
+        client_name: &str,
+    ) -> Result<AsyncGotify> {
+        let client = self
+            .execute(endpoints::login(
+                username.to_string(),
+                password.to_string(),
+                client_name.to_string(),
+            ))
+            .await?;
+        Ok(self.with_client_token(client.token))
     }
 
-    async fn get_current_user(&self) -> Result<User> {
-        self.do_request("get", "/current/user", None, None, None)
-            .await
+    /// Like `login`, but reuses the client token saved at `token_path` while the
+    /// server still accepts it, and saves a newly created token there.
+    pub async fn login_cached(
+        &self,
+        username: &str,
+        password: &str,
+        client_name: &str,
+        token_path: impl AsRef<Path>,
+    ) -> Result<AsyncGotify> {
+        let token_path = token_path.as_ref();
+        if let Some(token) = token_file::read_async(token_path).await? {
+            let gotify = self.with_client_token(token);
+            match gotify.get_current_user().await {
+                Ok(_) => return Ok(gotify),
+                Err(GotifyError::Unauthorized(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let gotify = self.login(username, password, client_name).await?;
+        if let Some(token) = gotify.gotify.client_token.as_deref() {
+            token_file::write_async(token_path, token).await?;
+        }
+        Ok(gotify)
     }
 
-    async fn set_password(&self, passwd: String) -> Result<()> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), Some(passwd));
-        self.do_request("get", "/current/user/password", Some(data), None, None)
-            .await
+    pub async fn update_client(&self, id: ClientId, name: String) -> Result<Client> {
+        self.execute(endpoints::update_client(id, name)).await
     }
 
-    async fn get_users(&self) -> Result<Vec<User>> {
-        self.do_request("get", "/user", None, None, None).await
+    pub async fn delete_client(&self, id: ClientId) -> Result<()> {
+        self.execute(endpoints::delete_client(id)).await
     }
 
-    async fn create_user(&self, name: String, passwd: String, admin: Option<bool>) -> Result<User> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), Some(passwd));
-        data.insert("name".to_string(), Some(name));
-        data.insert(
-            "admin".to_string(),
-            Some(if admin.unwrap_or(false) {
-                "true".to_string()
-            } else {
-                "false".to_string()
-            }),
-        );
-        self.do_request("post", "/user", Some(data), None, None)
-            .await
+    pub async fn get_current_user(&self) -> Result<User> {
+        self.execute(endpoints::get_current_user()).await
+    }
+
+    pub async fn set_password(&self, passwd: String) -> Result<()> {
+        self.execute(endpoints::set_password(passwd)).await
     }
 
-    async fn get_user(&self, id: i32) -> Result<User> {
-        self.do_request("get", &format!("/user/{id}"), None, None, None)
+    pub async fn get_users(&self) -> Result<Vec<User>> {
+        self.execute(endpoints::get_users()).await
+    }
+
+    pub async fn create_user(
+        &self,
+        name: String,
+        passwd: String,
+        admin: Option<bool>,
+    ) -> Result<User> {
+        self.execute(endpoints::create_user(name, passwd, admin))
             .await
     }
 
-    async fn update_user(
+    pub async fn get_user(&self, id: UserId) -> Result<User> {
+        self.execute(endpoints::get_user(id)).await
+    }
+
+    pub async fn update_user(
         &self,
-        id: i32,
+        id: UserId,
         name: Option<String>,
         passwd: Option<String>,
         admin: Option<bool>,
     ) -> Result<User> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), passwd);
-        data.insert("name".to_string(), name);
-        data.insert(
-            "admin".to_string(),
-            Some(if admin.unwrap_or(false) {
-                "true".to_string()
-            } else {
-                "false".to_string()
-            }),
-        );
-        self.do_request("put", &format!("/user/{id}"), Some(data), None, None)
+        self.execute(endpoints::update_user(id, name, passwd, admin))
             .await
     }
 
-    async fn delete_user(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/user/{id}"), None, None, None)
-            .await
+    pub async fn delete_user(&self, id: UserId) -> Result<()> {
+        self.execute(endpoints::delete_user(id)).await
     }
 
-    async fn get_health(&self) -> Result<Health> {
-        self.do_request("get", "/health", None, None, None).await
+    pub async fn get_health(&self) -> Result<Health> {
+        self.execute(endpoints::get_health()).await
     }
 
-    async fn get_plugins(&self) -> Result<Vec<PluginConf>> {
-        self.do_request("get", "/plugins", None, None, None).await
+    pub async fn get_plugins(&self) -> Result<Vec<PluginConf>> {
+        self.execute(endpoints::get_plugins()).await
     }
 
-    async fn get_plugin_config(&self, id: i32) -> Result<PluginConf> {
-        self.do_request("get", &format!("/plugins/{id}/config"), None, None, None)
-            .await
+    pub async fn get_plugin_config(&self, id: PluginId) -> Result<PluginConf> {
+        self.execute(endpoints::get_plugin_config(id)).await
     }
 
-    /// TODO(ethanhs): Figure out what this looks like
-    /* async fn update_plugin_config(&self, id: i32) {
+    // TODO(ethanhs): Figure out what this looks like
+    /* pub async fn update_plugin_config(&self, id: PluginId) {
         unimplemented!()
     } */
 
-    async fn disable_plugin(&self, id: i32) -> Result<()> {
-        self.do_request("post", &format!("/plugins/{id}/disable"), None, None, None)
-            .await
+    pub async fn disable_plugin(&self, id: PluginId) -> Result<()> {
+        self.execute(endpoints::disable_plugin(id)).await
     }
 
-    async fn get_plugin_display(&self, id: i32) -> Result<String> {
-        self.do_request("get", &format!("/plugins/{id}/display"), None, None, None)
-            .await
+    pub async fn get_plugin_display(&self, id: PluginId) -> Result<String> {
+        self.execute(endpoints::get_plugin_display(id)).await
     }
 
-    async fn enable_plugin(&self, id: i32) -> Result<()> {
-        self.do_request("post", &format!("/plugins/{id}/enable"), None, None, None)
-            .await
+    pub async fn enable_plugin(&self, id: PluginId) -> Result<()> {
+        self.execute(endpoints::enable_plugin(id)).await
     }
 
-    async fn get_version(&self) -> Result<VersionInfo> {
-        self.do_request("get", "/version", None, None, None).await
+    pub async fn get_version(&self) -> Result<VersionInfo> {
+        self.execute(endpoints::get_version()).await
     }
 }
@@ -0,0 +1,118 @@
+use reqwest::blocking::Client as SyncClient;
+#[cfg(feature = "async")]
+use reqwest::Client as AsyncClient;
+use url::Url;
+
+#[cfg(feature = "async")]
+use crate::AsyncGotify;
+use crate::{Gotify, GotifyError, Result, RetryPolicy, SyncGotify};
+
+/// Builds a `Gotify` configuration, or a client ready to use.
+pub struct GotifyBuilder {
+    base_url: String,
+    app_token: Option<String>,
+    client_token: Option<String>,
+    basic_auth: Option<(String, String)>,
+    retry: RetryPolicy,
+    sync_client: Option<SyncClient>,
+    #[cfg(feature = "async")]
+    async_client: Option<AsyncClient>,
+}
+
+impl GotifyBuilder {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            app_token: None,
+            client_token: None,
+            basic_auth: None,
+            retry: RetryPolicy::default(),
+            sync_client: None,
+            #[cfg(feature = "async")]
+            async_client: None,
+        }
+    }
+
+    pub fn app_token(mut self, token: impl Into<String>) -> Self {
+        self.app_token = Some(token.into());
+        self
+    }
+
+    pub fn client_token(mut self, token: impl Into<String>) -> Self {
+        self.client_token = Some(token.into());
+        self
+    }
+
+    /// Authenticates client-token endpoints with a user's credentials when no
+    /// client token is set.
+    pub fn basic_auth(mut self, user: impl Into<String>, pass: impl Into<String>) -> Self {
+        self.basic_auth = Some((user.into(), pass.into()));
+        self
+    }
+
+    /// Retries failed requests as `policy` says. Defaults to
+    /// `RetryPolicy::default()`, which retries GET and DELETE requests but
+    /// never message sends; `RetryPolicy::none()` turns retries off.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Sets the `reqwest::blocking::Client` used by `build_sync`.
+    pub fn blocking_client(mut self, client: SyncClient) -> Self {
+        self.sync_client = Some(client);
+        self
+    }
+
+    /// Sets the `reqwest::Client` used by `build_async`.
+    #[cfg(feature = "async")]
+    pub fn async_client(mut self, client: AsyncClient) -> Self {
+        self.async_client = Some(client);
+        self
+    }
+
+    pub fn build(self) -> Result<Gotify> {
+        let mut base_url = Url::parse(&self.base_url)
+            .map_err(|e| GotifyError::InvalidUrl(format!("{}: {e}", self.base_url)))?;
+        if !matches!(base_url.scheme(), "http" | "https") {
+            return Err(GotifyError::InvalidUrl(format!(
+                "{}: scheme must be http or https",
+                self.base_url
+            )));
+        }
+        // Endpoint paths are joined relative to the base, so it has to end in a
+        // slash for a sub-path prefix like `/gotify` to be kept.
+        if !base_url.path().ends_with('/') {
+            let path = format!("{}/", base_url.path());
+            base_url.set_path(&path);
+        }
+        base_url.set_query(None);
+        base_url.set_fragment(None);
+        Ok(Gotify {
+            base_url,
+            app_token: self.app_token,
+            client_token: self.client_token,
+            basic_auth: self.basic_auth,
+            retry: self.retry,
+        })
+    }
+
+    pub fn build_sync(mut self) -> Result<SyncGotify> {
+        let client = self.sync_client.take();
+        let gotify = self.build()?;
+        Ok(match client {
+            Some(client) => SyncGotify::with_client(gotify, client),
+            None => SyncGotify::from(gotify),
+        })
+    }
+
+    #[cfg(feature = "async")]
+    pub fn build_async(mut self) -> Result<AsyncGotify> {
+        let client = self.async_client.take();
+        let gotify = self.build()?;
+        Ok(match client {
+            Some(client) => AsyncGotify::with_client(gotify, client),
+            None => AsyncGotify::from(gotify),
+        })
+    }
+}
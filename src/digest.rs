@@ -0,0 +1,191 @@
+//! Batching low-priority messages into a periodic summary.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use crate::error::{GotifyError, Result};
+use crate::message::NewMessage;
+use crate::priority::Priority;
+use crate::response_types::Message;
+use crate::sync::{lock, ErrorHandler};
+use crate::AsyncGotify;
+
+/// When and how `Digest` summarises messages.
+#[derive(Debug, Clone)]
+pub struct DigestPolicy {
+    /// Messages with a priority below this are held for the digest.
+    pub threshold: Priority,
+    /// Anything shorter than a millisecond, including zero, is raised to one.
+    pub interval: Duration,
+    /// The title of the summary message.
+    pub title: String,
+}
+
+/// The shortest interval a `Digest` summarises at; the timer can't tick
+/// every zero seconds.
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+impl Default for DigestPolicy {
+    fn default() -> Self {
+        Self {
+            threshold: Priority::NORMAL,
+            interval: Duration::from_secs(60 * 60),
+            title: "Digest".to_string(),
+        }
+    }
+}
+
+/// What `Digest` did with a message.
+#[derive(Debug)]
+pub enum Digested {
+    Sent(Message),
+    /// Held for the next summary.
+    Batched,
+}
+
+/// Holds back low-priority messages and sends them as one markdown summary,
+/// grouped by title, at the end of each interval.
+///
+/// Messages at or above the threshold, and those without a priority (which
+/// the server decides), are sent straight away. The summary goes out from a
+/// background task, so a `Digest` must be created within a tokio runtime;
+/// the task stops once every clone has been dropped, discarding anything not
+/// yet summarised unless `flush` is called first. Failures to send a summary
+/// at the end of an interval are passed to `on_error`.
+#[derive(Clone)]
+pub struct Digest {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    client: AsyncGotify,
+    policy: DigestPolicy,
+    batch: Mutex<Vec<NewMessage>>,
+    on_error: Mutex<Option<ErrorHandler>>,
+}
+
+impl Digest {
+    pub fn new(client: AsyncGotify, mut policy: DigestPolicy) -> Self {
+        policy.interval = policy.interval.max(MIN_INTERVAL);
+        let shared = Arc::new(Shared {
+            client,
+            policy,
+            batch: Mutex::default(),
+            on_error: Mutex::default(),
+        });
+        tokio::spawn(summarise_every(Arc::downgrade(&shared)));
+        Self { shared }
+    }
+
+    /// Called with each summary the server didn't accept.
+    pub fn on_error(
+        self,
+        on_error: impl Fn(NewMessage, GotifyError) + Send + Sync + 'static,
+    ) -> Self {
+        *lock(&self.shared.on_error) = Some(Arc::new(on_error));
+        self
+    }
+
+    pub async fn create_message(
+        &self,
+        message: String,
+        priority: Option<Priority>,
+        title: Option<String>,
+    ) -> Result<Digested> {
+        self.send_message(NewMessage {
+            message,
+            title,
+            priority,
+            extras: Default::default(),
+        })
+        .await
+    }
+
+    pub async fn send_message(&self, message: impl Into<NewMessage>) -> Result<Digested> {
+        let message = message.into();
+        match message.priority {
+            Some(priority) if priority < self.shared.policy.threshold => {
+                lock(&self.shared.batch).push(message);
+                Ok(Digested::Batched)
+            }
+            _ => self
+                .shared
+                .client
+                .send_message(message)
+                .await
+                .map(Digested::Sent),
+        }
+    }
+
+    /// The number of messages waiting for the next summary.
+    pub fn pending(&self) -> usize {
+        lock(&self.shared.batch).len()
+    }
+
+    /// Sends the summary now instead of at the end of the interval. Returns
+    /// `None` when nothing was waiting; failures are returned rather than
+    /// passed to `on_error`.
+    pub async fn flush(&self) -> Option<Result<Message>> {
+        self.shared.summarise().await.map(|(_, result)| result)
+    }
+}
+
+impl Shared {
+    /// Sends the summary of everything batched so far, if there is any.
+    async fn summarise(&self) -> Option<(NewMessage, Result<Message>)> {
+        let batch = std::mem::take(&mut *lock(&self.batch));
+        let summary = render(&self.policy.title, batch)?;
+        let result = self.client.send_message(summary.clone()).await;
+        Some((summary, result))
+    }
+}
+
+async fn summarise_every(shared: Weak<Shared>) {
+    let Some(interval) = shared.upgrade().map(|s| s.policy.interval) else {
+        return;
+    };
+    let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    loop {
+        ticks.tick().await;
+        let Some(shared) = shared.upgrade() else {
+            return;
+        };
+        if let Some((summary, Err(e))) = shared.summarise().await {
+            let on_error = lock(&shared.on_error).clone();
+            if let Some(on_error) = on_error {
+                on_error(summary, e);
+            }
+        }
+    }
+}
+
+/// The summary of `batch`, with one section per title in order of first
+/// appearance. It takes the highest priority in the batch.
+fn render(title: &str, batch: Vec<NewMessage>) -> Option<NewMessage> {
+    let priority = batch.iter().filter_map(|m| m.priority).max()?;
+    let mut groups: Vec<(Option<String>, Vec<String>)> = Vec::new();
+    for message in batch {
+        match groups.iter_mut().find(|(t, _)| *t == message.title) {
+            Some((_, bodies)) => bodies.push(message.message),
+            None => groups.push((message.title, vec![message.message])),
+        }
+    }
+    let mut body = String::new();
+    for (title, bodies) in groups {
+        let title = title.as_deref().unwrap_or("Untitled");
+        let _ = write!(body, "### {title} ({})\n\n", bodies.len());
+        for text in bodies {
+            // Continuation lines are indented to stay inside the list item.
+            let _ = writeln!(body, "- {}", text.trim_end().replace('\n', "\n  "));
+        }
+        body.push('\n');
+    }
+    Some(
+        NewMessage::builder(body.trim_end())
+            .title(title)
+            .priority(priority)
+            .markdown()
+            .build(),
+    )
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::ops::{Bound, RangeBounds};
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, StreamExt};
+use regex::Regex;
+use tokio::sync::Mutex;
+
+use crate::error::{GotifyError, Result};
+use crate::ids::{AppId, MessageId};
+use crate::priority::Priority;
+use crate::response_types::Message;
+use crate::AsyncGotify;
+
+/// What a handler returns once it has processed a message.
+pub type HandlerResult = std::result::Result<Disposition, Box<dyn StdError + Send + Sync>>;
+
+type Handler = Arc<dyn Fn(Message) -> BoxFuture<'static, HandlerResult> + Send + Sync>;
+type ErrorHandler = Arc<dyn Fn(DispatchError) + Send + Sync>;
+
+/// What to do with a message after its handler succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    Keep,
+    /// Delete the message from the server.
+    Delete,
+}
+
+/// A failure reported to `Dispatcher::on_error`.
+#[derive(Debug, thiserror::Error)]
+pub enum DispatchError {
+    #[error("handler failed for message {message_id}: {error}")]
+    Handler {
+        message_id: MessageId,
+        error: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("failed to delete message {message_id}: {error}")]
+    Delete {
+        message_id: MessageId,
+        error: GotifyError,
+    },
+    /// The message stream yielded an error, or application names couldn't be resolved.
+    #[error(transparent)]
+    Gotify(#[from] GotifyError),
+}
+
+/// Which messages a handler receives. Every condition set must match.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    app_ids: Vec<AppId>,
+    app_names: Vec<String>,
+    priority: (Bound<Priority>, Bound<Priority>),
+    title: Option<Regex>,
+    extras: Vec<String>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            app_ids: Vec::new(),
+            app_names: Vec::new(),
+            priority: (Bound::Unbounded, Bound::Unbounded),
+            title: None,
+            extras: Vec::new(),
+        }
+    }
+}
+
+impl Filter {
+    /// Matches every message.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Matches messages from this application; repeat to accept several.
+    pub fn app_id(mut self, id: AppId) -> Self {
+        self.app_ids.push(id);
+        self
+    }
+
+    /// Matches messages from the application with this name; repeat to accept
+    /// several. Names are resolved through `GET /application`.
+    pub fn app_name(mut self, name: impl Into<String>) -> Self {
+        self.app_names.push(name.into());
+        self
+    }
+
+    pub fn priority(mut self, range: impl RangeBounds<Priority>) -> Self {
+        self.priority = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    pub fn title(mut self, pattern: Regex) -> Self {
+        self.title = Some(pattern);
+        self
+    }
+
+    /// Matches messages carrying this extras namespace, e.g. `"client::display"`.
+    pub fn has_extra(mut self, namespace: impl Into<String>) -> Self {
+        self.extras.push(namespace.into());
+        self
+    }
+
+    fn matches(&self, message: &Message, app_name: Option<&str>) -> bool {
+        let app_matches = (self.app_ids.is_empty() && self.app_names.is_empty())
+            || self.app_ids.contains(&message.appid)
+            || app_name.is_some_and(|name| self.app_names.iter().any(|n| n == name));
+        app_matches
+            && self.priority.contains(&message.priority_level())
+            && self
+                .title
+                .as_ref()
+                .is_none_or(|title| title.is_match(message.title.as_deref().unwrap_or_default()))
+            && self
+                .extras
+                .iter()
+                .all(|namespace| message.extras.contains_key(namespace))
+    }
+}
+
+/// Routes messages from a stream to handlers.
+///
+/// Each message goes to the first route whose filter matches it; messages no
+/// route matches are dropped. Handlers run concurrently, up to the configured
+/// limit, and failures are passed to `on_error`. Without it they are dropped.
+///
+/// ```no_run
+/// # async fn example(gotify: gotify_rs::AsyncGotify) {
+/// use gotify_rs::{Disposition, Dispatcher, Filter, Priority};
+///
+/// let dispatcher = Dispatcher::new(gotify.clone())
+///     .route(Filter::any().app_name("backups").priority(Priority::HIGH..), |message| async move {
+///         println!("backup alert: {}", message.message);
+///         Ok(Disposition::Delete)
+///     })
+///     .on_error(|error| eprintln!("{error}"));
+/// dispatcher.run(gotify.subscribe(Default::default())).await;
+/// # }
+/// ```
+pub struct Dispatcher {
+    client: AsyncGotify,
+    routes: Vec<(Filter, Handler)>,
+    on_error: Option<ErrorHandler>,
+    concurrency: usize,
+}
+
+impl Dispatcher {
+    pub fn new(client: AsyncGotify) -> Self {
+        Self {
+            client,
+            routes: Vec::new(),
+            on_error: None,
+            concurrency: 1,
+        }
+    }
+
+    /// How many handlers may run at once. Defaults to 1, which handles
+    /// messages in order.
+    pub fn concurrency(mut self, limit: usize) -> Self {
+        self.concurrency = limit.max(1);
+        self
+    }
+
+    pub fn route<F, Fut>(mut self, filter: Filter, handler: F) -> Self
+    where
+        F: Fn(Message) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = HandlerResult> + Send + 'static,
+    {
+        let handler: Handler = Arc::new(move |message| handler(message).boxed());
+        self.routes.push((filter, handler));
+        self
+    }
+
+    pub fn on_error(mut self, on_error: impl Fn(DispatchError) + Send + Sync + 'static) -> Self {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Dispatches every message from `messages`, returning once it ends and
+    /// all handlers have finished.
+    pub async fn run(self, messages: impl Stream<Item = Result<Message>> + Send) {
+        let app_names = AppNames::new(
+            self.client.clone(),
+            self.routes.iter().any(|(f, _)| !f.app_names.is_empty()),
+        );
+        let this = &self;
+        let app_names = &app_names;
+        messages
+            .for_each_concurrent(self.concurrency, |message| async move {
+                let result = match message {
+                    Ok(message) => this.dispatch(message, app_names).await,
+                    Err(e) => Err(e.into()),
+                };
+                if let Err(e) = result {
+                    this.report(e);
+                }
+            })
+            .await;
+    }
+
+    async fn dispatch(
+        &self,
+        message: Message,
+        app_names: &AppNames,
+    ) -> std::result::Result<(), DispatchError> {
+        // Without a name only name-based filters miss, so keep routing.
+        let app_name = app_names.get(message.appid).await.unwrap_or_else(|e| {
+            self.report(e.into());
+            None
+        });
+        let Some((_, handler)) = self
+            .routes
+            .iter()
+            .find(|(filter, _)| filter.matches(&message, app_name.as_deref()))
+        else {
+            return Ok(());
+        };
+        let message_id = message.id;
+        match handler(message).await {
+            Ok(Disposition::Keep) => Ok(()),
+            Ok(Disposition::Delete) => self
+                .client
+                .delete_message(message_id)
+                .await
+                .map_err(|error| DispatchError::Delete { message_id, error }),
+            Err(error) => Err(DispatchError::Handler { message_id, error }),
+        }
+    }
+
+    fn report(&self, error: DispatchError) {
+        if let Some(on_error) = &self.on_error {
+            on_error(error);
+        }
+    }
+}
+
+/// Application names by id, fetched only when a route filters by name and
+/// refreshed when a message arrives from an application not seen before.
+/// Ids still missing after a refresh, such as deleted applications, are
+/// remembered so they don't trigger another one.
+struct AppNames {
+    client: AsyncGotify,
+    enabled: bool,
+    names: Mutex<HashMap<AppId, Option<String>>>,
+}
+
+impl AppNames {
+    fn new(client: AsyncGotify, enabled: bool) -> Self {
+        Self {
+            client,
+            enabled,
+            names: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn get(&self, app_id: AppId) -> Result<Option<String>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+        let mut names = self.names.lock().await;
+        if let Some(name) = names.get(&app_id) {
+            return Ok(name.clone());
+        }
+        let apps = self.client.applications().await?;
+        // Keep the misses learned so far; the fresh list overrides any that
+        // have since appeared.
+        names.retain(|_, name| name.is_none());
+        names.extend(apps.into_iter().map(|app| (app.id, Some(app.name))));
+        Ok(names.entry(app_id).or_insert(None).clone())
+    }
+}
@@ -0,0 +1,633 @@
+//! Every Gotify endpoint described once, and executed by both `SyncGotify` and `AsyncGotify`.
+//!
+//! Each `Endpoint` records the HTTP method, a path template such as
+//! `/application/{id}`, the credential it needs and how its parameters are
+//! sent. The response type is carried as a type parameter. The functions at
+//! the bottom of this module turn method arguments into a `Call`, which the
+//! clients then send with their own HTTP client.
+
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{decode_response, GotifyError, Result};
+use crate::ids::*;
+use crate::image::{DownloadedImage, Image};
+use crate::message::NewMessage;
+use crate::request_types::*;
+use crate::response_types::*;
+
+/// Which credential a request is authenticated with.
+///
+/// `AppToken` and `ClientToken` use the tokens the client was configured with;
+/// endpoints that take a client token also accept the configured basic-auth
+/// user. `Basic` carries its own credentials.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Auth {
+    AppToken,
+    ClientToken,
+    Basic { user: String, pass: String },
+    None,
+}
+
+impl fmt::Debug for Auth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Auth::AppToken => f.write_str("AppToken"),
+            Auth::ClientToken => f.write_str("ClientToken"),
+            Auth::Basic { user, .. } => f
+                .debug_struct("Basic")
+                .field("user", user)
+                .finish_non_exhaustive(),
+            Auth::None => f.write_str("None"),
+        }
+    }
+}
+
+/// How an endpoint's parameters are sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Payload {
+    None,
+    Query,
+    Json,
+    File,
+}
+
+/// The untyped description of an endpoint.
+#[derive(Debug)]
+pub struct EndpointInfo {
+    pub name: &'static str,
+    pub method: Method,
+    pub path: &'static str,
+    pub auth: Auth,
+    pub payload: Payload,
+}
+
+/// An endpoint together with the type its response decodes into.
+pub struct Endpoint<R> {
+    pub info: EndpointInfo,
+    response: PhantomData<fn() -> R>,
+}
+
+impl<R> Endpoint<R> {
+    const fn new(
+        name: &'static str,
+        method: Method,
+        path: &'static str,
+        auth: Auth,
+        payload: Payload,
+    ) -> Self {
+        Self {
+            info: EndpointInfo {
+                name,
+                method,
+                path,
+                auth,
+                payload,
+            },
+            response: PhantomData,
+        }
+    }
+
+    /// The name of the type the response decodes into.
+    pub fn response_type(&self) -> &'static str {
+        std::any::type_name::<R>()
+    }
+
+    /// Starts a call, filling the `{name}` placeholders of the path template.
+    pub(crate) fn call(&self, params: &[(&str, &dyn Display)]) -> Call<R> {
+        let mut path = self.info.path.to_string();
+        for (name, value) in params {
+            path = path.replace(&format!("{{{name}}}"), &value.to_string());
+        }
+        Call {
+            method: self.info.method.clone(),
+            path,
+            auth: self.info.auth.clone(),
+            payload: self.info.payload,
+            query: None,
+            body: Body::Empty,
+            sends_message: false,
+            response: PhantomData,
+        }
+    }
+}
+
+pub const LIST_APPLICATIONS: Endpoint<Vec<Application>> = Endpoint::new(
+    "applications",
+    Method::GET,
+    "/application",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const CREATE_APPLICATION: Endpoint<Application> = Endpoint::new(
+    "create_application",
+    Method::POST,
+    "/application",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const UPDATE_APPLICATION: Endpoint<Application> = Endpoint::new(
+    "update_application",
+    Method::PUT,
+    "/application/{id}",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const DELETE_APPLICATION: Endpoint<()> = Endpoint::new(
+    "delete_application",
+    Method::DELETE,
+    "/application/{id}",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const UPLOAD_APPLICATION_IMAGE: Endpoint<Application> = Endpoint::new(
+    "upload_application_image",
+    Method::POST,
+    "/application/{id}/image",
+    Auth::ClientToken,
+    Payload::File,
+);
+pub const DELETE_APPLICATION_IMAGE: Endpoint<()> = Endpoint::new(
+    "delete_application_image",
+    Method::DELETE,
+    "/application/{id}/image",
+    Auth::ClientToken,
+    Payload::None,
+);
+/// An application's image, at the path given by `Application::image`.
+pub const APPLICATION_IMAGE: Endpoint<DownloadedImage> = Endpoint::new(
+    "get_application_image",
+    Method::GET,
+    "/{image}",
+    Auth::None,
+    Payload::None,
+);
+pub const LIST_MESSAGES: Endpoint<PagedMessages> = Endpoint::new(
+    "get_messages",
+    Method::GET,
+    "/message",
+    Auth::ClientToken,
+    Payload::Query,
+);
+pub const LIST_APPLICATION_MESSAGES: Endpoint<PagedMessages> = Endpoint::new(
+    "get_application_messages",
+    Method::GET,
+    "/application/{id}/message",
+    Auth::ClientToken,
+    Payload::Query,
+);
+pub const CREATE_MESSAGE: Endpoint<Message> = Endpoint::new(
+    "create_message",
+    Method::POST,
+    "/message",
+    Auth::AppToken,
+    Payload::Json,
+);
+pub const DELETE_MESSAGES: Endpoint<()> = Endpoint::new(
+    "delete_messages",
+    Method::DELETE,
+    "/message",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const DELETE_APPLICATION_MESSAGES: Endpoint<()> = Endpoint::new(
+    "delete_application_messages",
+    Method::DELETE,
+    "/application/{id}/message",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const DELETE_MESSAGE: Endpoint<()> = Endpoint::new(
+    "delete_message",
+    Method::DELETE,
+    "/message/{id}",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const LIST_CLIENTS: Endpoint<Vec<Client>> = Endpoint::new(
+    "get_clients",
+    Method::GET,
+    "/client",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const CREATE_CLIENT: Endpoint<Client> = Endpoint::new(
+    "create_client",
+    Method::POST,
+    "/client",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const UPDATE_CLIENT: Endpoint<Client> = Endpoint::new(
+    "update_client",
+    Method::PUT,
+    "/client/{id}",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const DELETE_CLIENT: Endpoint<()> = Endpoint::new(
+    "delete_client",
+    Method::DELETE,
+    "/client/{id}",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const CURRENT_USER: Endpoint<User> = Endpoint::new(
+    "get_current_user",
+    Method::GET,
+    "/current/user",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const SET_PASSWORD: Endpoint<()> = Endpoint::new(
+    "set_password",
+    Method::POST,
+    "/current/user/password",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const LIST_USERS: Endpoint<Vec<User>> = Endpoint::new(
+    "get_users",
+    Method::GET,
+    "/user",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const CREATE_USER: Endpoint<User> = Endpoint::new(
+    "create_user",
+    Method::POST,
+    "/user",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const GET_USER: Endpoint<User> = Endpoint::new(
+    "get_user",
+    Method::GET,
+    "/user/{id}",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const UPDATE_USER: Endpoint<User> = Endpoint::new(
+    "update_user",
+    Method::PUT,
+    "/user/{id}",
+    Auth::ClientToken,
+    Payload::Json,
+);
+pub const DELETE_USER: Endpoint<()> = Endpoint::new(
+    "delete_user",
+    Method::DELETE,
+    "/user/{id}",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const HEALTH: Endpoint<Health> = Endpoint::new(
+    "get_health",
+    Method::GET,
+    "/health",
+    Auth::None,
+    Payload::None,
+);
+pub const LIST_PLUGINS: Endpoint<Vec<PluginConf>> = Endpoint::new(
+    "get_plugins",
+    Method::GET,
+    "/plugin",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const PLUGIN_CONFIG: Endpoint<PluginConf> = Endpoint::new(
+    "get_plugin_config",
+    Method::GET,
+    "/plugin/{id}/config",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const DISABLE_PLUGIN: Endpoint<()> = Endpoint::new(
+    "disable_plugin",
+    Method::POST,
+    "/plugin/{id}/disable",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const PLUGIN_DISPLAY: Endpoint<String> = Endpoint::new(
+    "get_plugin_display",
+    Method::GET,
+    "/plugin/{id}/display",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const ENABLE_PLUGIN: Endpoint<()> = Endpoint::new(
+    "enable_plugin",
+    Method::POST,
+    "/plugin/{id}/enable",
+    Auth::ClientToken,
+    Payload::None,
+);
+pub const VERSION: Endpoint<VersionInfo> = Endpoint::new(
+    "get_version",
+    Method::GET,
+    "/version",
+    Auth::None,
+    Payload::None,
+);
+/// The WebSocket that pushes each new message as it arrives.
+pub const STREAM: Endpoint<Message> = Endpoint::new(
+    "stream",
+    Method::GET,
+    "/stream",
+    Auth::ClientToken,
+    Payload::None,
+);
+
+/// Every endpoint this crate knows about.
+pub const ENDPOINTS: &[&EndpointInfo] = &[
+    &LIST_APPLICATIONS.info,
+    &CREATE_APPLICATION.info,
+    &UPDATE_APPLICATION.info,
+    &DELETE_APPLICATION.info,
+    &UPLOAD_APPLICATION_IMAGE.info,
+    &DELETE_APPLICATION_IMAGE.info,
+    &APPLICATION_IMAGE.info,
+    &LIST_MESSAGES.info,
+    &LIST_APPLICATION_MESSAGES.info,
+    &CREATE_MESSAGE.info,
+    &DELETE_MESSAGES.info,
+    &DELETE_APPLICATION_MESSAGES.info,
+    &DELETE_MESSAGE.info,
+    &LIST_CLIENTS.info,
+    &CREATE_CLIENT.info,
+    &UPDATE_CLIENT.info,
+    &DELETE_CLIENT.info,
+    &CURRENT_USER.info,
+    &SET_PASSWORD.info,
+    &LIST_USERS.info,
+    &CREATE_USER.info,
+    &GET_USER.info,
+    &UPDATE_USER.info,
+    &DELETE_USER.info,
+    &HEALTH.info,
+    &LIST_PLUGINS.info,
+    &PLUGIN_CONFIG.info,
+    &DISABLE_PLUGIN.info,
+    &PLUGIN_DISPLAY.info,
+    &ENABLE_PLUGIN.info,
+    &VERSION.info,
+    &STREAM.info,
+];
+
+/// Turns a response into an endpoint's response type.
+pub(crate) trait FromResponse: Sized {
+    fn from_response(status: StatusCode, headers: &HeaderMap, body: Vec<u8>) -> Result<Self>;
+}
+
+impl<T: DeserializeOwned> FromResponse for T {
+    fn from_response(status: StatusCode, _: &HeaderMap, body: Vec<u8>) -> Result<Self> {
+        decode_response(status, &body)
+    }
+}
+
+/// Images are passed on as raw bytes rather than decoded.
+impl FromResponse for DownloadedImage {
+    fn from_response(status: StatusCode, headers: &HeaderMap, body: Vec<u8>) -> Result<Self> {
+        if !status.is_success() {
+            return Err(GotifyError::from_response(status, &body));
+        }
+        let content_type = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        Ok(DownloadedImage {
+            bytes: body,
+            content_type,
+        })
+    }
+}
+
+pub(crate) enum Body {
+    Empty,
+    Json(Value),
+    /// A multipart form holding the image as its only file field.
+    Image {
+        field: &'static str,
+        image: Image,
+    },
+}
+
+/// A single request to an endpoint, ready to be sent by either client.
+pub(crate) struct Call<R> {
+    pub(crate) method: Method,
+    pub(crate) path: String,
+    pub(crate) auth: Auth,
+    /// The endpoint's declared payload, which the body builders check against.
+    payload: Payload,
+    pub(crate) query: Option<Value>,
+    pub(crate) body: Body,
+    /// Sends a message, which `RetryPolicy::messages` decides whether to retry.
+    pub(crate) sends_message: bool,
+    response: PhantomData<fn() -> R>,
+}
+
+impl<R> Call<R> {
+    fn auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    fn query(mut self, query: impl Serialize) -> Self {
+        debug_assert_eq!(self.payload, Payload::Query, "{} takes no query", self.path);
+        self.query = Some(
+            serde_json::to_value(query)
+                .expect("query parameters have string keys and always serialize"),
+        );
+        self
+    }
+
+    fn json(mut self, data: impl Serialize) -> Self {
+        debug_assert_eq!(
+            self.payload,
+            Payload::Json,
+            "{} takes no JSON body",
+            self.path
+        );
+        self.body = Body::Json(
+            serde_json::to_value(data)
+                .expect("request bodies have string keys and always serialize"),
+        );
+        self
+    }
+
+    fn image(mut self, field: &'static str, image: Image) -> Self {
+        debug_assert_eq!(self.payload, Payload::File, "{} takes no file", self.path);
+        self.body = Body::Image { field, image };
+        self
+    }
+
+    fn sends_message(mut self) -> Self {
+        self.sends_message = true;
+        self
+    }
+}
+
+pub(crate) fn applications() -> Call<Vec<Application>> {
+    LIST_APPLICATIONS.call(&[])
+}
+
+pub(crate) fn create_application(name: String, description: String) -> Call<Application> {
+    CREATE_APPLICATION.call(&[]).json(ApplicationParams {
+        name,
+        description: Some(description),
+    })
+}
+
+pub(crate) fn update_application(
+    id: AppId,
+    name: String,
+    description: Option<String>,
+) -> Call<Application> {
+    UPDATE_APPLICATION
+        .call(&[("id", &id)])
+        .json(ApplicationParams { name, description })
+}
+
+pub(crate) fn delete_application(id: AppId) -> Call<()> {
+    DELETE_APPLICATION.call(&[("id", &id)])
+}
+
+pub(crate) fn upload_application_image(id: AppId, image: Image) -> Call<Application> {
+    UPLOAD_APPLICATION_IMAGE
+        .call(&[("id", &id)])
+        .image("file", image)
+}
+
+pub(crate) fn delete_application_image(id: AppId) -> Call<()> {
+    DELETE_APPLICATION_IMAGE.call(&[("id", &id)])
+}
+
+pub(crate) fn download_application_image(app: &Application) -> Call<DownloadedImage> {
+    APPLICATION_IMAGE.call(&[("image", &app.image.trim_start_matches('/'))])
+}
+
+pub(crate) fn get_messages(
+    app_id: Option<AppId>,
+    limit: Option<i32>,
+    since: Option<MessageId>,
+) -> Call<PagedMessages> {
+    let call = match app_id {
+        Some(id) => LIST_APPLICATION_MESSAGES.call(&[("id", &id)]),
+        None => LIST_MESSAGES.call(&[]),
+    };
+    call.query(MessagesQuery { limit, since })
+}
+
+pub(crate) fn create_message(message: NewMessage) -> Call<Message> {
+    CREATE_MESSAGE.call(&[]).json(message).sends_message()
+}
+
+pub(crate) fn delete_messages(app_id: Option<AppId>) -> Call<()> {
+    match app_id {
+        Some(id) => DELETE_APPLICATION_MESSAGES.call(&[("id", &id)]),
+        None => DELETE_MESSAGES.call(&[]),
+    }
+}
+
+pub(crate) fn delete_message(msg_id: MessageId) -> Call<()> {
+    DELETE_MESSAGE.call(&[("id", &msg_id)])
+}
+
+pub(crate) fn get_clients() -> Call<Vec<Client>> {
+    LIST_CLIENTS.call(&[])
+}
+
+pub(crate) fn create_client(name: String) -> Call<Client> {
+    CREATE_CLIENT.call(&[]).json(ClientParams { name })
+}
+
+/// Creates a client authenticated with a user's credentials rather than the configured ones.
+pub(crate) fn login(user: String, pass: String, client_name: String) -> Call<Client> {
+    create_client(client_name).auth(Auth::Basic { user, pass })
+}
+
+pub(crate) fn update_client(id: ClientId, name: String) -> Call<Client> {
+    UPDATE_CLIENT
+        .call(&[("id", &id)])
+        .json(ClientParams { name })
+}
+
+pub(crate) fn delete_client(id: ClientId) -> Call<()> {
+    DELETE_CLIENT.call(&[("id", &id)])
+}
+
+pub(crate) fn get_current_user() -> Call<User> {
+    CURRENT_USER.call(&[])
+}
+
+pub(crate) fn set_password(passwd: String) -> Call<()> {
+    SET_PASSWORD.call(&[]).json(SetPassword { pass: passwd })
+}
+
+pub(crate) fn get_users() -> Call<Vec<User>> {
+    LIST_USERS.call(&[])
+}
+
+pub(crate) fn create_user(name: String, passwd: String, admin: Option<bool>) -> Call<User> {
+    CREATE_USER.call(&[]).json(CreateUser {
+        name,
+        pass: passwd,
+        admin: admin.unwrap_or(false),
+    })
+}
+
+pub(crate) fn get_user(id: UserId) -> Call<User> {
+    GET_USER.call(&[("id", &id)])
+}
+
+pub(crate) fn update_user(
+    id: UserId,
+    name: Option<String>,
+    passwd: Option<String>,
+    admin: Option<bool>,
+) -> Call<User> {
+    UPDATE_USER.call(&[("id", &id)]).json(UpdateUser {
+        name,
+        pass: passwd,
+        admin,
+    })
+}
+
+pub(crate) fn delete_user(id: UserId) -> Call<()> {
+    DELETE_USER.call(&[("id", &id)])
+}
+
+pub(crate) fn get_health() -> Call<Health> {
+    HEALTH.call(&[])
+}
+
+pub(crate) fn get_plugins() -> Call<Vec<PluginConf>> {
+    LIST_PLUGINS.call(&[])
+}
+
+pub(crate) fn get_plugin_config(id: PluginId) -> Call<PluginConf> {
+    PLUGIN_CONFIG.call(&[("id", &id)])
+}
+
+pub(crate) fn disable_plugin(id: PluginId) -> Call<()> {
+    DISABLE_PLUGIN.call(&[("id", &id)])
+}
+
+pub(crate) fn get_plugin_display(id: PluginId) -> Call<String> {
+    PLUGIN_DISPLAY.call(&[("id", &id)])
+}
+
+pub(crate) fn enable_plugin(id: PluginId) -> Call<()> {
+    ENABLE_PLUGIN.call(&[("id", &id)])
+}
+
+pub(crate) fn get_version() -> Call<VersionInfo> {
+    VERSION.call(&[])
+}
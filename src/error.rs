@@ -0,0 +1,109 @@
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use crate::response_types::Error;
+
+pub type Result<T> = std::result::Result<T, GotifyError>;
+
+/// Errors returned by the Gotify clients.
+///
+/// Non-2xx responses are decoded from Gotify's `Error` body and mapped onto a
+/// variant by status code, so callers can match on the failure directly.
+#[derive(Debug, thiserror::Error)]
+pub enum GotifyError {
+    /// The base URL, or a path from the server resolved under it, isn't valid.
+    #[error("invalid URL: {0}")]
+    InvalidUrl(String),
+    #[error("missing credentials: this endpoint needs a {0}")]
+    MissingCredentials(&'static str),
+    #[error("bad request: {}", .0.description)]
+    BadRequest(Error),
+    #[error("unauthorized: {}", .0.description)]
+    Unauthorized(Error),
+    #[error("forbidden: {}", .0.description)]
+    Forbidden(Error),
+    #[error("not found: {}", .0.description)]
+    NotFound(Error),
+    #[error("server returned {code}: {description}")]
+    Server { code: u16, description: String },
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    #[error("failed to decode response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("websocket error: {0}")]
+    WebSocket(#[from] Box<tungstenite::Error>),
+    /// Nothing arrived within the read timeout; the connection is still usable.
+    #[error("timed out waiting for the server")]
+    Timeout,
+    /// The bytes given to `Image` aren't in a format it recognises.
+    #[error("unsupported image format: expected PNG, JPEG or GIF")]
+    UnsupportedImage,
+}
+
+impl From<tungstenite::Error> for GotifyError {
+    fn from(error: tungstenite::Error) -> Self {
+        GotifyError::WebSocket(Box::new(error))
+    }
+}
+
+impl GotifyError {
+    /// Builds the error for a non-2xx response from its status and raw body.
+    pub(crate) fn from_response(status: StatusCode, body: &[u8]) -> Self {
+        let error = serde_json::from_slice::<Error>(body).unwrap_or_else(|_| Error {
+            error: status.canonical_reason().unwrap_or_default().to_string(),
+            code: status.as_u16() as i32,
+            description: String::from_utf8_lossy(body).into_owned(),
+            extra: Default::default(),
+        });
+        match status {
+            StatusCode::BAD_REQUEST => GotifyError::BadRequest(error),
+            StatusCode::UNAUTHORIZED => GotifyError::Unauthorized(error),
+            StatusCode::FORBIDDEN => GotifyError::Forbidden(error),
+            StatusCode::NOT_FOUND => GotifyError::NotFound(error),
+            _ => GotifyError::Server {
+                code: status.as_u16(),
+                description: error.description,
+            },
+        }
+    }
+
+    /// The HTTP status code of the response, if the server answered at all.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            GotifyError::BadRequest(_) => Some(400),
+            GotifyError::Unauthorized(_) => Some(401),
+            GotifyError::Forbidden(_) => Some(403),
+            GotifyError::NotFound(_) => Some(404),
+            GotifyError::Server { code, .. } => Some(*code),
+            GotifyError::Transport(e) => e.status().map(|s| s.as_u16()),
+            GotifyError::InvalidUrl(_)
+            | GotifyError::MissingCredentials(_)
+            | GotifyError::Decode(_)
+            | GotifyError::Io(_)
+            | GotifyError::Timeout
+            | GotifyError::UnsupportedImage => None,
+            GotifyError::WebSocket(e) => match e.as_ref() {
+                tungstenite::Error::Http(response) => Some(response.status().as_u16()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// Turns a response status and body into either the decoded value or a `GotifyError`.
+///
+/// Endpoints that answer with an empty body (e.g. deletes) decode as `null`,
+/// which lets them be typed as `()`.
+pub(crate) fn decode_response<T: DeserializeOwned>(status: StatusCode, body: &[u8]) -> Result<T> {
+    if !status.is_success() {
+        return Err(GotifyError::from_response(status, body));
+    }
+    let body = if body.iter().all(u8::is_ascii_whitespace) {
+        b"null".as_slice()
+    } else {
+        body
+    };
+    Ok(serde_json::from_slice(body)?)
+}
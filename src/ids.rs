@@ -0,0 +1,35 @@
+//! Typed ids, so an id of one kind of object can't be passed where another is expected.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! ids {
+    ($($(#[$meta:meta])* $name:ident;)*) => {$(
+        $(#[$meta])*
+        #[derive(
+            Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize,
+        )]
+        #[serde(transparent)]
+        pub struct $name(pub i32);
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    )*};
+}
+
+ids! {
+    /// The id of an `Application`.
+    AppId;
+    /// The id of a `Client`.
+    ClientId;
+    /// The id of a `User`.
+    UserId;
+    /// The id of a `Message`. Newer messages have larger ids.
+    MessageId;
+    /// The id of a plugin, as in `PluginConf`.
+    PluginId;
+}
@@ -0,0 +1,131 @@
+//! Application images, uploaded to Gotify as a multipart form.
+
+use std::io::Read;
+use std::path::Path;
+
+use crate::error::{GotifyError, Result};
+
+/// The formats Gotify accepts, recognised by their leading magic bytes, with
+/// the file extensions it allows for each. The first is used for nameless
+/// images.
+const SIGNATURES: &[(&[u8], &str, &[&str])] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png", &["png"]),
+    (b"\xff\xd8\xff", "image/jpeg", &["jpg", "jpeg"]),
+    (b"GIF87a", "image/gif", &["gif"]),
+    (b"GIF89a", "image/gif", &["gif"]),
+];
+
+fn sniff(bytes: &[u8]) -> Option<(&'static str, &'static [&'static str])> {
+    SIGNATURES
+        .iter()
+        .find(|(magic, _, _)| bytes.starts_with(magic))
+        .map(|(_, mime, extensions)| (*mime, *extensions))
+}
+
+/// An image to upload with `upload_application_image`.
+///
+/// The MIME type is sniffed from the image's magic bytes, and bytes in any
+/// format but PNG, JPEG or GIF are rejected with
+/// `GotifyError::UnsupportedImage`. Gotify only accepts files named with an
+/// image extension, so images read from a path or without a name get one
+/// matching the sniffed type.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    bytes: Vec<u8>,
+    file_name: String,
+    mime_type: &'static str,
+    extensions: &'static [&'static str],
+}
+
+impl Image {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        let (mime_type, extensions) = sniff(&bytes).ok_or(GotifyError::UnsupportedImage)?;
+        Ok(Self {
+            bytes,
+            file_name: format!("image.{}", extensions[0]),
+            mime_type,
+            extensions,
+        })
+    }
+
+    /// Reads the image from disk, keeping the file's stem. Its extension is
+    /// kept too if it matches the sniffed type, and replaced otherwise.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let image = Self::from_bytes(std::fs::read(path)?)?;
+        Ok(image.with_name_of(path))
+    }
+
+    pub fn from_reader(mut reader: impl Read) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(bytes)
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn from_path_async(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let image = Self::from_bytes(tokio::fs::read(path).await?)?;
+        Ok(image.with_name_of(path))
+    }
+
+    #[cfg(feature = "async")]
+    pub async fn from_async_reader(mut reader: impl tokio::io::AsyncRead + Unpin) -> Result<Self> {
+        use tokio::io::AsyncReadExt;
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Self::from_bytes(bytes)
+    }
+
+    /// Overrides the file name sent with the upload.
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = file_name.into();
+        self
+    }
+
+    fn with_name_of(self, path: &Path) -> Self {
+        let Some(stem) = path.file_stem() else {
+            return self;
+        };
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_ascii_lowercase())
+            .filter(|e| self.extensions.contains(&e.as_str()))
+            .unwrap_or_else(|| self.extensions[0].to_string());
+        let file_name = format!("{}.{extension}", stem.to_string_lossy());
+        self.file_name(file_name)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.file_name
+    }
+
+    pub fn mime_type(&self) -> &str {
+        self.mime_type
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<u8>, String, &'static str) {
+        (self.bytes, self.file_name, self.mime_type)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Image {
+    type Error = GotifyError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self> {
+        Self::from_bytes(bytes)
+    }
+}
+
+/// An application image fetched from the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadedImage {
+    pub bytes: Vec<u8>,
+    /// The `Content-Type` the server sent, if any.
+    pub content_type: Option<String>,
+}
@@ -1,300 +1,463 @@
-use std::collections::HashMap;
-use std::fs::File;
-use std::str::FromStr;
+use std::path::Path;
+use std::sync::Arc;
 
 use reqwest::blocking::Client as SyncClient;
-use reqwest::Method;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
 
-use anyhow::Result;
-use serde::de::DeserializeOwned;
+use url::Url;
 
+#[cfg(feature = "async")]
 mod async_gotify;
+mod builder;
+#[cfg(feature = "async")]
+mod digest;
+#[cfg(feature = "async")]
+mod dispatcher;
+pub mod endpoints;
+mod error;
+mod ids;
+mod image;
+mod message;
+mod outbox;
+mod paging;
+mod priority;
+mod request_types;
 mod response_types;
+mod retry;
+mod sender;
+mod stream;
+#[cfg(feature = "async")]
+mod subscription;
+mod sync;
+#[cfg(feature = "async")]
+mod throttle;
+mod timestamp;
+mod token_file;
+#[cfg(feature = "async")]
 pub use crate::async_gotify::*;
+pub use crate::builder::*;
+#[cfg(feature = "async")]
+pub use crate::digest::*;
+#[cfg(feature = "async")]
+pub use crate::dispatcher::*;
+pub use crate::endpoints::Auth;
+use crate::endpoints::{Body, Call, FromResponse};
+pub use crate::error::*;
+pub use crate::ids::*;
+pub use crate::image::{DownloadedImage, Image};
+pub use crate::message::*;
+pub use crate::outbox::{Delivery, Flushed, Outbox};
+use crate::paging::Pager;
+pub use crate::paging::*;
+pub use crate::priority::*;
 pub use crate::response_types::*;
-
-pub struct Gotify<'a> {
-    base_url: &'a str,
-    app_token: Option<&'a str>,
-    client_token: Option<&'a str>,
+use crate::retry::Outcome;
+pub use crate::retry::{MessageRetry, RetryPolicy, DEDUP_EXTRA};
+pub use crate::sender::*;
+pub use crate::stream::*;
+#[cfg(feature = "async")]
+pub use crate::subscription::*;
+#[cfg(feature = "async")]
+pub use crate::throttle::*;
+pub use crate::timestamp::*;
+
+/// Connection settings shared by `SyncGotify` and `AsyncGotify`.
+#[derive(Clone)]
+pub struct Gotify {
+    base_url: Url,
+    app_token: Option<String>,
+    client_token: Option<String>,
+    basic_auth: Option<(String, String)>,
+    retry: RetryPolicy,
 }
 
-impl<'a> Gotify<'a> {
-    pub fn new(base_url: &'a str, app_token: Option<&'a str>, client_token: Option<&'a str>) -> Self {
-        Self {
-            base_url: base_url.trim_end_matches("/"),
-            app_token: app_token,
-            client_token: client_token,
+impl Gotify {
+    pub fn new(
+        base_url: &str,
+        app_token: Option<&str>,
+        client_token: Option<&str>,
+    ) -> Result<Self> {
+        let mut builder = Gotify::builder(base_url);
+        if let Some(token) = app_token {
+            builder = builder.app_token(token);
         }
+        if let Some(token) = client_token {
+            builder = builder.client_token(token);
+        }
+        builder.build()
     }
-    pub fn config(mut self, base_url: &'a str, app_token: &'a str, client_token: &'a str) -> Self {
-        self.base_url = base_url.trim_end_matches("/");
-        self.app_token = Some(app_token);
-        self.client_token = Some(client_token);
-        self
+
+    pub fn builder(base_url: impl Into<String>) -> GotifyBuilder {
+        GotifyBuilder::new(base_url)
+    }
+
+    /// Resolves an endpoint path such as `/application/1` under the base URL,
+    /// keeping any sub-path prefix the server is mounted at. Paths can come
+    /// from the server, as with application images, so absolute URLs and
+    /// paths that resolve outside the base URL are rejected.
+    fn url(&self, path: &str) -> Result<Url> {
+        let invalid = |reason: String| GotifyError::InvalidUrl(format!("{path}: {reason}"));
+        if Url::parse(path).is_ok() {
+            return Err(invalid("not a relative path".to_string()));
+        }
+        let url = self
+            .base_url
+            .join(path.trim_start_matches('/'))
+            .map_err(|e| invalid(e.to_string()))?;
+        if url.origin() != self.base_url.origin() || !url.path().starts_with(self.base_url.path()) {
+            return Err(invalid("outside the base URL".to_string()));
+        }
+        Ok(url)
+    }
+
+    /// Picks the credentials to send for a request that needs `auth`.
+    fn credentials<'a>(&'a self, auth: &'a Auth) -> Result<Credentials<'a>> {
+        match auth {
+            Auth::AppToken => self
+                .app_token
+                .as_deref()
+                .map(Credentials::Token)
+                .ok_or(GotifyError::MissingCredentials("app token")),
+            Auth::ClientToken => match (&self.client_token, &self.basic_auth) {
+                (Some(token), _) => Ok(Credentials::Token(token)),
+                (None, Some((user, pass))) => Ok(Credentials::Basic(user, pass)),
+                (None, None) => Err(GotifyError::MissingCredentials(
+                    "client token or basic auth",
+                )),
+            },
+            Auth::Basic { user, pass } => Ok(Credentials::Basic(user, pass)),
+            Auth::None => Ok(Credentials::None),
+        }
     }
 }
 
-pub struct SyncGotify<'a> {
-    gotify: Gotify<'a>,
+pub(crate) enum Credentials<'a> {
+    Token(&'a str),
+    Basic(&'a str, &'a str),
+    None,
+}
+
+/// A blocking Gotify client.
+///
+/// Cloning is cheap: clones share the configuration and the connection pool.
+#[derive(Clone)]
+pub struct SyncGotify {
+    gotify: Arc<Gotify>,
     client: SyncClient,
 }
 
-impl<'a> SyncGotify<'a> {
-    pub fn new(base_url: &'a str, app_token: Option<&'a str>, client_token: Option<&'a str>) -> Self {
-        let gotify = Gotify::new(base_url, app_token, client_token);
-        let client = SyncClient::new();
-        Self { gotify, client }
+impl SyncGotify {
+    pub fn new(
+        base_url: &str,
+        app_token: Option<&str>,
+        client_token: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self::from(Gotify::new(base_url, app_token, client_token)?))
     }
-    pub fn from(gotify: Gotify<'a>) -> Self {
-        let client = SyncClient::new();
-        Self { gotify, client }
+    pub fn from(gotify: Gotify) -> Self {
+        Self::with_client(gotify, SyncClient::new())
+    }
+    /// Uses a preconfigured `reqwest::blocking::Client`, e.g. for proxies, timeouts or TLS settings.
+    pub fn with_client(gotify: Gotify, client: SyncClient) -> Self {
+        Self {
+            gotify: Arc::new(gotify),
+            client,
+        }
     }
 
-    fn get_token(&self, auth_mode: Option<&str>) -> Option<&'a str> {
-        if let Some(mode) = auth_mode {
-            if mode == "app" {
-                self.gotify.app_token
-            } else {
-                self.gotify.client_token
-            }
-        } else {
-            self.gotify.app_token
+    /// Returns a client that sends `token` as the app token, sharing this client's connection pool.
+    pub fn with_app_token(&self, token: impl Into<String>) -> Self {
+        self.with_config(|gotify| gotify.app_token = Some(token.into()))
+    }
+
+    /// Returns a client that sends `token` as the client token, sharing this client's connection pool.
+    pub fn with_client_token(&self, token: impl Into<String>) -> Self {
+        self.with_config(|gotify| gotify.client_token = Some(token.into()))
+    }
+
+    /// Returns a client that authenticates client-token endpoints with basic auth instead,
+    /// sharing this client's connection pool.
+    pub fn with_basic_auth(&self, user: impl Into<String>, pass: impl Into<String>) -> Self {
+        self.with_config(|gotify| {
+            gotify.client_token = None;
+            gotify.basic_auth = Some((user.into(), pass.into()));
+        })
+    }
+
+    /// Returns a client that retries failed requests as `policy` says, sharing this client's
+    /// connection pool.
+    pub fn with_retry_policy(&self, policy: RetryPolicy) -> Self {
+        self.with_config(|gotify| gotify.retry = policy)
+    }
+
+    fn with_config(&self, update: impl FnOnce(&mut Gotify)) -> Self {
+        let mut gotify = (*self.gotify).clone();
+        update(&mut gotify);
+        Self {
+            gotify: Arc::new(gotify),
+            client: self.client.clone(),
         }
     }
 
-    fn do_request<T: DeserializeOwned>(
-        &self,
-        method: &str,
-        endpoint_url: &str,
-        data: Option<HashMap<String, Option<String>>>,
-        file: Option<File>,
-        auth_mode: Option<&str>,
-    ) -> Result<T> {
-        let method = Method::from_str(&method)?;
-        let request_url = format!("{}{}", self.gotify.base_url, endpoint_url);
-
-        let mut request = self.client.request(method.clone(), request_url);
-        if let Some(f) = file {
-            request = request.body(f);
-        } else {
-            if let Some(data) = data {
-                match method {
-                    Method::GET => {
-                        request = request.query(&data);
-                    }
-                    _ => {
-                        request = request.json(&data);
-                    }
+    fn execute<T: FromResponse>(&self, call: Call<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            let response = self.send(&call);
+            let outcome = match &response {
+                Ok((status, _, _)) if status.is_success() => None,
+                Ok((status, headers, _)) => Some(Outcome::Status(*status, headers)),
+                Err(GotifyError::Transport(_)) => Some(Outcome::Transport),
+                Err(_) => None,
+            };
+            let delay = outcome.and_then(|outcome| {
+                self.gotify
+                    .retry
+                    .delay(&call.method, call.sends_message, attempt, outcome)
+            });
+            match delay {
+                Some(delay) => std::thread::sleep(delay),
+                None => {
+                    let (status, headers, body) = response?;
+                    return T::from_response(status, &headers, body);
                 }
-                request = request.json(&data);
             }
+            attempt += 1;
+        }
+    }
+
+    /// Sends one attempt of `call`; the call is kept so it can be sent again.
+    fn send<T>(&self, call: &Call<T>) -> Result<(StatusCode, HeaderMap, Vec<u8>)> {
+        let request_url = self.gotify.url(&call.path)?;
+        let mut request = self.client.request(call.method.clone(), request_url);
+        if let Some(query) = &call.query {
+            request = request.query(query);
+        }
+        match &call.body {
+            Body::Empty => {}
+            Body::Json(data) => request = request.json(data),
+            Body::Image { field, image } => {
+                let (bytes, file_name, mime_type) = image.clone().into_parts();
+                let part = reqwest::blocking::multipart::Part::bytes(bytes)
+                    .file_name(file_name)
+                    .mime_str(mime_type)?;
+                request =
+                    request.multipart(reqwest::blocking::multipart::Form::new().part(*field, part));
+            }
+        }
+        match self.gotify.credentials(&call.auth)? {
+            Credentials::Token(token) => request = request.header("X-Gotify-Key", token),
+            Credentials::Basic(user, pass) => request = request.basic_auth(user, Some(pass)),
+            Credentials::None => {}
         }
-        let token = self.get_token(auth_mode).expect("missing token");
-        request = request.header("X-Gotify-Key", token);
         let response = request.send()?;
-        Ok(response.json::<T>()?)
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.bytes()?;
+        Ok((status, headers, body.to_vec()))
     }
 
     pub fn applications(&self) -> Result<Vec<Application>> {
-        self.do_request("get", "/application", None, None, None)
+        self.execute(endpoints::applications())
     }
 
     pub fn create_application(&self, name: String, description: String) -> Result<Application> {
-        let mut data = HashMap::new();
-        data.insert("name".to_owned(), Some(name));
-        data.insert("description".to_owned(), Some(description));
-        self.do_request("post", "/application", Some(data), None, None)
+        self.execute(endpoints::create_application(name, description))
     }
 
     pub fn update_application(
         &self,
-        id: i32,
+        id: AppId,
         name: String,
         description: Option<String>,
     ) -> Result<Application> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        data.insert("description".to_string(), description);
-        self.do_request("put", &format!("/application/{id}"), Some(data), None, None)
+        self.execute(endpoints::update_application(id, name, description))
     }
 
-    pub fn delete_application(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/application/{id}"), None, None, None)
+    pub fn delete_application(&self, id: AppId) -> Result<()> {
+        self.execute(endpoints::delete_application(id))
     }
 
-    pub fn upload_application_image(&self, id: i32, image: File) -> Result<Application> {
-        self.do_request(
-            "post",
-            &format!("/application/{id}/image"),
-            None,
-            Some(image),
-            None,
-        )
+    /// Uploads the application's image, e.g. `Image::from_path("icon.png")?`.
+    pub fn upload_application_image(&self, id: AppId, image: Image) -> Result<Application> {
+        self.execute(endpoints::upload_application_image(id, image))
+    }
+
+    pub fn delete_application_image(&self, id: AppId) -> Result<()> {
+        self.execute(endpoints::delete_application_image(id))
+    }
+
+    /// Fetches the image at `app.image`, which is relative to the base URL.
+    pub fn download_application_image(&self, app: &Application) -> Result<DownloadedImage> {
+        self.execute(endpoints::download_application_image(app))
     }
 
     pub fn get_messages(
         &self,
-        app_id: Option<i32>,
+        app_id: Option<AppId>,
         limit: Option<i32>,
-        since: Option<i32>,
+        since: Option<MessageId>,
     ) -> Result<PagedMessages> {
-        let mut data = HashMap::new();
-        data.insert("limit".to_string(), limit.map(|i| i.to_string()));
-        data.insert("since".to_string(), since.map(|i| i.to_string()));
-        if let Some(id) = app_id {
-            self.do_request(
-                "get",
-                &format!("/application/{id}/message"),
-                Some(data),
-                None,
-                None,
-            )
-        } else {
-            self.do_request("get", "/message", Some(data), None, None)
-        }
+        self.execute(endpoints::get_messages(app_id, limit, since))
+    }
+
+    /// Iterates over all messages, or those of one application, newest first,
+    /// fetching `page_size` messages per request.
+    pub fn messages_iter(&self, app_id: Option<AppId>, page_size: i32) -> MessagesIter {
+        MessagesIter::new(self.clone(), Pager::new(app_id, page_size))
     }
 
     pub fn create_message(
         &self,
         message: String,
-        priority: Option<i32>,
+        priority: Option<Priority>,
         title: Option<String>,
-        // TODO: extras
     ) -> Result<Message> {
-        let mut data = HashMap::new();
-        data.insert("message".to_string(), Some(message));
-        data.insert("priority".to_string(), priority.map(|i| i.to_string()));
-        data.insert("title".to_string(), title);
-        self.do_request("post", "/message", Some(data), None, Some("app"))
-    }
-
-    pub fn delete_messages(&self, app_id: Option<i32>) -> Result<()> {
-        if let Some(id) = app_id {
-            self.do_request(
-                "delete",
-                &format!("/application/{id}/message"),
-                None,
-                None,
-                None,
-            )
-        } else {
-            self.do_request("delete", &format!("/message"), None, None, None)
-        }
+        self.send_message(NewMessage {
+            message,
+            title,
+            priority,
+            extras: Default::default(),
+        })
+    }
+
+    /// Sends a message built with `MessageBuilder`, including its extras.
+    pub fn send_message(&self, message: impl Into<NewMessage>) -> Result<Message> {
+        let mut message = message.into();
+        self.gotify.retry.prepare_message(&mut message);
+        self.execute(endpoints::create_message(message))
+    }
+
+    pub fn delete_messages(&self, app_id: Option<AppId>) -> Result<()> {
+        self.execute(endpoints::delete_messages(app_id))
     }
 
-    pub fn delete_message(&self, msg_id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/message/{msg_id}"), None, None, None)
+    pub fn delete_message(&self, msg_id: MessageId) -> Result<()> {
+        self.execute(endpoints::delete_message(msg_id))
     }
 
     pub fn get_clients(&self) -> Result<Vec<Client>> {
-        self.do_request("get", "/client", None, None, None)
+        self.execute(endpoints::get_clients())
     }
 
     pub fn create_client(&self, name: String) -> Result<Client> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        self.do_request("post", "/client", Some(data), None, None)
+        self.execute(endpoints::create_client(name))
     }
 
-    pub fn update_client(&self, id: i32, name: String) -> Result<Client> {
-        let mut data = HashMap::new();
-        data.insert("name".to_string(), Some(name));
-        self.do_request("put", &format!("/client/{id}"), Some(data), None, None)
+    /// Creates a client named `client_name` using the user's credentials and
+    /// returns a `SyncGotify` authenticated with the new client token.
+    pub fn login(&self, username: &str, password: &str, client_name: &str) -> Result<SyncGotify> {
+        let client = self.execute(endpoints::login(
+            username.to_string(),
+            password.to_string(),
+            client_name.to_string(),
+        ))?;
+        Ok(self.with_client_token(client.token))
     }
 
-    pub fn delete_client(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/client/{id}"), None, None, None)
+    /// Like `login`, but reuses the client token saved at `token_path` while the
+    /// server still accepts it, and saves a newly created token there.
+    pub fn login_cached(
+        &self,
+        username: &str,
+        password: &str,
+        client_name: &str,
+        token_path: impl AsRef<Path>,
+    ) -> Result<SyncGotify> {
+        let token_path = token_path.as_ref();
+        if let Some(token) = token_file::read(token_path)? {
+            let gotify = self.with_client_token(token);
+            match gotify.get_current_user() {
+                Ok(_) => return Ok(gotify),
+                Err(GotifyError::Unauthorized(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let gotify = self.login(username, password, client_name)?;
+        if let Some(token) = gotify.gotify.client_token.as_deref() {
+            token_file::write(token_path, token)?;
+        }
+        Ok(gotify)
+    }
+
+    pub fn update_client(&self, id: ClientId, name: String) -> Result<Client> {
+        self.execute(endpoints::update_client(id, name))
+    }
+
+    pub fn delete_client(&self, id: ClientId) -> Result<()> {
+        self.execute(endpoints::delete_client(id))
     }
 
     pub fn get_current_user(&self) -> Result<User> {
-        self.do_request("get", "/current/user", None, None, None)
+        self.execute(endpoints::get_current_user())
     }
 
     pub fn set_password(&self, passwd: String) -> Result<()> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), Some(passwd));
-        self.do_request("get", "/current/user/password", Some(data), None, None)
+        self.execute(endpoints::set_password(passwd))
     }
 
     pub fn get_users(&self) -> Result<Vec<User>> {
-        self.do_request("get", "/user", None, None, None)
+        self.execute(endpoints::get_users())
     }
 
     pub fn create_user(&self, name: String, passwd: String, admin: Option<bool>) -> Result<User> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), Some(passwd));
-        data.insert("name".to_string(), Some(name));
-        data.insert(
-            "admin".to_string(),
-            Some(if admin.unwrap_or(false) {
-                "true".to_string()
-            } else {
-                "false".to_string()
-            }),
-        );
-        self.do_request("post", "/user", Some(data), None, None)
+        self.execute(endpoints::create_user(name, passwd, admin))
     }
 
-    pub fn get_user(&self, id: i32) -> Result<User> {
-        self.do_request("get", &format!("/user/{id}"), None, None, None)
+    pub fn get_user(&self, id: UserId) -> Result<User> {
+        self.execute(endpoints::get_user(id))
     }
 
     pub fn update_user(
         &self,
-        id: i32,
+        id: UserId,
         name: Option<String>,
         passwd: Option<String>,
         admin: Option<bool>,
     ) -> Result<User> {
-        let mut data = HashMap::new();
-        data.insert("pass".to_string(), passwd);
-        data.insert("name".to_string(), name);
-        data.insert(
-            "admin".to_string(),
-            Some(if admin.unwrap_or(false) {
-                "true".to_string()
-            } else {
-                "false".to_string()
-            }),
-        );
-        self.do_request("put", &format!("/user/{id}"), Some(data), None, None)
+        self.execute(endpoints::update_user(id, name, passwd, admin))
     }
 
-    pub fn delete_user(&self, id: i32) -> Result<()> {
-        self.do_request("delete", &format!("/user/{id}"), None, None, None)
+    pub fn delete_user(&self, id: UserId) -> Result<()> {
+        self.execute(endpoints::delete_user(id))
     }
 
     pub fn get_health(&self) -> Result<Health> {
-        self.do_request("get", "/health", None, None, None)
+        self.execute(endpoints::get_health())
     }
 
     pub fn get_plugins(&self) -> Result<Vec<PluginConf>> {
-        self.do_request("get", "/plugins", None, None, None)
+        self.execute(endpoints::get_plugins())
     }
 
-    pub fn get_plugin_config(&self, id: i32) -> Result<PluginConf> {
-        self.do_request("get", &format!("/plugins/{id}/config"), None, None, None)
+    pub fn get_plugin_config(&self, id: PluginId) -> Result<PluginConf> {
+        self.execute(endpoints::get_plugin_config(id))
     }
 
-    /// TODO(ethanhs): Figure out what this looks like
-    /* pub fn update_plugin_config(&self, id: i32) {
+    // TODO(ethanhs): Figure out what this looks like
+    /* pub fn update_plugin_config(&self, id: PluginId) {
         unimplemented!()
     } */
 
-    pub fn disable_plugin(&self, id: i32) -> Result<()> {
-        self.do_request("post", &format!("/plugins/{id}/disable"), None, None, None)
+    pub fn disable_plugin(&self, id: PluginId) -> Result<()> {
+        self.execute(endpoints::disable_plugin(id))
     }
 
-    pub fn get_plugin_display(&self, id: i32) -> Result<String> {
-        self.do_request("get", &format!("/plugins/{id}/display"), None, None, None)
+    pub fn get_plugin_display(&self, id: PluginId) -> Result<String> {
+        self.execute(endpoints::get_plugin_display(id))
     }
 
-    pub fn enable_plugin(&self, id: i32) -> Result<()> {
-        self.do_request("post", &format!("/plugins/{id}/enable"), None, None, None)
+    pub fn enable_plugin(&self, id: PluginId) -> Result<()> {
+        self.execute(endpoints::enable_plugin(id))
     }
 
     pub fn get_version(&self) -> Result<VersionInfo> {
-        self.do_request("get", "/version", None, None, None)
+        self.execute(endpoints::get_version())
+    }
+
+    /// Opens the `/stream` WebSocket and yields each new message as the server
+    /// pushes it, blocking the calling thread while waiting.
+    pub fn stream(&self) -> Result<BlockingMessageStream> {
+        BlockingMessageStream::connect(&self.gotify)
     }
 }
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::priority::Priority;
+use crate::response_types::Message;
+
+pub const CLIENT_DISPLAY: &str = "client::display";
+pub const CLIENT_NOTIFICATION: &str = "client::notification";
+pub const ANDROID_ACTION: &str = "android::action";
+
+/// How clients should render the message body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ContentType {
+    #[serde(rename = "text/plain")]
+    Plain,
+    #[serde(rename = "text/markdown")]
+    Markdown,
+}
+
+/// The `client::display` extras.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ClientDisplay {
+    #[serde(rename = "contentType", skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<ContentType>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Click {
+    pub url: String,
+}
+
+/// The `client::notification` extras.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct ClientNotification {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub click: Option<Click>,
+    #[serde(rename = "bigImageUrl", skip_serializing_if = "Option::is_none")]
+    pub big_image_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct OnReceive {
+    #[serde(rename = "intentUrl")]
+    pub intent_url: String,
+}
+
+/// The `android::action` extras.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct AndroidAction {
+    #[serde(rename = "onReceive", skip_serializing_if = "Option::is_none")]
+    pub on_receive: Option<OnReceive>,
+}
+
+/// A typed view over a message's extras.
+///
+/// Namespaces this crate doesn't know about are kept in `other`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Extras {
+    pub display: Option<ClientDisplay>,
+    pub notification: Option<ClientNotification>,
+    pub android_action: Option<AndroidAction>,
+    pub other: HashMap<String, Value>,
+}
+
+impl Extras {
+    pub fn from_map(extras: &HashMap<String, Value>) -> Self {
+        fn typed<T: DeserializeOwned>(value: &Value) -> Option<T> {
+            serde_json::from_value(value.clone()).ok()
+        }
+        let mut view = Extras::default();
+        for (namespace, value) in extras {
+            // A known namespace with an unexpected shape is kept as-is in `other`.
+            let parsed = match namespace.as_str() {
+                CLIENT_DISPLAY => typed(value).map(|d| view.display = Some(d)),
+                CLIENT_NOTIFICATION => typed(value).map(|n| view.notification = Some(n)),
+                ANDROID_ACTION => typed(value).map(|a| view.android_action = Some(a)),
+                _ => None,
+            };
+            if parsed.is_none() {
+                view.other.insert(namespace.clone(), value.clone());
+            }
+        }
+        view
+    }
+
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        let mut extras = self.other.clone();
+        let typed = [
+            (
+                CLIENT_DISPLAY,
+                self.display.as_ref().map(serde_json::to_value),
+            ),
+            (
+                CLIENT_NOTIFICATION,
+                self.notification.as_ref().map(serde_json::to_value),
+            ),
+            (
+                ANDROID_ACTION,
+                self.android_action.as_ref().map(serde_json::to_value),
+            ),
+        ];
+        for (namespace, value) in typed {
+            if let Some(Ok(value)) = value {
+                extras.insert(namespace.to_string(), value);
+            }
+        }
+        extras
+    }
+
+    pub fn content_type(&self) -> Option<ContentType> {
+        self.display.as_ref().and_then(|d| d.content_type)
+    }
+
+    pub fn click_url(&self) -> Option<&str> {
+        self.notification
+            .as_ref()
+            .and_then(|n| n.click.as_ref())
+            .map(|c| c.url.as_str())
+    }
+
+    pub fn big_image_url(&self) -> Option<&str> {
+        self.notification
+            .as_ref()
+            .and_then(|n| n.big_image_url.as_deref())
+    }
+
+    pub fn intent_url(&self) -> Option<&str> {
+        self.android_action
+            .as_ref()
+            .and_then(|a| a.on_receive.as_ref())
+            .map(|r| r.intent_url.as_str())
+    }
+}
+
+impl Message {
+    /// The message's priority, clamped into `0..=10`.
+    pub fn priority_level(&self) -> Priority {
+        Priority::saturating(self.priority)
+    }
+
+    /// The message's extras, decoded into the namespaces Gotify's clients understand.
+    pub fn typed_extras(&self) -> Extras {
+        Extras::from_map(&self.extras)
+    }
+}
+
+/// The body of a `POST /message` request.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NewMessage {
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extras: HashMap<String, Value>,
+}
+
+impl NewMessage {
+    pub fn builder(message: impl Into<String>) -> MessageBuilder {
+        MessageBuilder::new(message)
+    }
+}
+
+/// Builds a `NewMessage`, including the extras Gotify's clients understand.
+#[derive(Debug, Clone)]
+pub struct MessageBuilder {
+    message: String,
+    title: Option<String>,
+    priority: Option<Priority>,
+    extras: Extras,
+}
+
+impl MessageBuilder {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            title: None,
+            priority: None,
+            extras: Extras::default(),
+        }
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn content_type(mut self, content_type: ContentType) -> Self {
+        self.extras
+            .display
+            .get_or_insert_with(Default::default)
+            .content_type = Some(content_type);
+        self
+    }
+
+    pub fn markdown(self) -> Self {
+        self.content_type(ContentType::Markdown)
+    }
+
+    pub fn plain(self) -> Self {
+        self.content_type(ContentType::Plain)
+    }
+
+    /// The URL opened when the notification is clicked.
+    pub fn click_url(mut self, url: impl Into<String>) -> Self {
+        self.extras
+            .notification
+            .get_or_insert_with(Default::default)
+            .click = Some(Click { url: url.into() });
+        self
+    }
+
+    pub fn big_image_url(mut self, url: impl Into<String>) -> Self {
+        self.extras
+            .notification
+            .get_or_insert_with(Default::default)
+            .big_image_url = Some(url.into());
+        self
+    }
+
+    /// The URL the Android app opens as soon as the message is received.
+    pub fn intent_url(mut self, url: impl Into<String>) -> Self {
+        self.extras
+            .android_action
+            .get_or_insert_with(Default::default)
+            .on_receive = Some(OnReceive {
+            intent_url: url.into(),
+        });
+        self
+    }
+
+    /// Sets an arbitrary extra such as `"home::appliance"`. Namespaces should
+    /// follow Gotify's `<top-level-namespace>::[<sub-namespace>::]<action>` format.
+    /// The typed setters above take precedence over a raw value for the same namespace.
+    pub fn extra(mut self, namespace: impl Into<String>, value: Value) -> Self {
+        self.extras.other.insert(namespace.into(), value);
+        self
+    }
+
+    pub fn build(self) -> NewMessage {
+        NewMessage {
+            message: self.message,
+            title: self.title,
+            priority: self.priority,
+            extras: self.extras.to_map(),
+        }
+    }
+}
+
+impl From<MessageBuilder> for NewMessage {
+    fn from(builder: MessageBuilder) -> Self {
+        builder.build()
+    }
+}
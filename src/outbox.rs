@@ -0,0 +1,279 @@
+//! Queueing messages on disk while the server can't be reached.
+
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{GotifyError, Result};
+use crate::message::NewMessage;
+use crate::response_types::Message;
+use crate::sync::lock;
+use crate::token_file;
+use crate::SyncGotify;
+
+/// A line of the outbox log.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Record {
+    Queued {
+        seq: u64,
+        app_token: Option<String>,
+        message: NewMessage,
+    },
+    /// The queued message was delivered, or rejected and dropped.
+    Done { seq: u64 },
+}
+
+#[derive(Debug)]
+struct Queued {
+    seq: u64,
+    app_token: Option<String>,
+    message: NewMessage,
+}
+
+/// What became of a message passed to `Outbox::send`.
+#[derive(Debug)]
+pub enum Delivery {
+    Sent(Message),
+    /// The server couldn't be reached; the message waits on disk.
+    Queued,
+}
+
+/// The result of `Outbox::flush`.
+#[derive(Debug, Default)]
+pub struct Flushed {
+    pub sent: usize,
+    /// Messages the server refused, e.g. because the app token was revoked.
+    /// They are removed from the queue, since sending them again can't work.
+    pub rejected: Vec<(NewMessage, GotifyError)>,
+}
+
+/// Sends messages through a `SyncGotify`, keeping those that can't be
+/// delivered in an append-only log until the server is back.
+///
+/// Each queued message is stored with the app token it was sent with, and
+/// queued messages are replayed in order once `get_health` reports the
+/// server healthy: by `flush`, and by `send` at most once per
+/// `replay_interval`. While anything is still queued, new messages queue up
+/// behind it so the order is kept. The log is compacted when opened.
+pub struct Outbox {
+    client: SyncGotify,
+    path: PathBuf,
+    state: Mutex<State>,
+    /// The queue length, readable without waiting for a send to finish.
+    depth: AtomicUsize,
+    replay_interval: Duration,
+}
+
+struct State {
+    queue: VecDeque<Queued>,
+    next_seq: u64,
+    log: File,
+    last_replay: Option<Instant>,
+    /// Messages rejected while `send` replayed the queue, kept for `flush`.
+    rejected: Vec<(NewMessage, GotifyError)>,
+}
+
+impl Outbox {
+    /// Opens the outbox logged at `path`, creating it if needed and
+    /// restoring anything queued by a previous run.
+    pub fn open(client: SyncGotify, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let queue = read_log(&path)?;
+        let next_seq = queue.back().map_or(0, |q| q.seq + 1);
+        let log = compact(&path, &queue)?;
+        Ok(Self {
+            client,
+            path,
+            depth: AtomicUsize::new(queue.len()),
+            state: Mutex::new(State {
+                queue,
+                next_seq,
+                log,
+                last_replay: None,
+                rejected: Vec::new(),
+            }),
+            replay_interval: Duration::from_secs(10),
+        })
+    }
+
+    /// How often `send` tries to replay the queue before sending. Defaults
+    /// to 10 seconds, so a server that is down isn't asked for its health on
+    /// every send.
+    pub fn replay_interval(mut self, interval: Duration) -> Self {
+        self.replay_interval = interval;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The number of messages waiting to be sent. Never waits for a send or
+    /// flush in progress.
+    pub fn depth(&self) -> usize {
+        self.depth.load(Ordering::Relaxed)
+    }
+
+    /// Sends the message, or queues it if the server can't be reached.
+    ///
+    /// If messages are already queued they are replayed first, when
+    /// `replay_interval` has passed since the last attempt. Errors other than
+    /// the server being unreachable, such as an invalid app token, are
+    /// returned and nothing is queued.
+    pub fn send(&self, message: impl Into<NewMessage>) -> Result<Delivery> {
+        let message = message.into();
+        let mut state = self.state();
+        let replay_due = state
+            .last_replay
+            .is_none_or(|at| at.elapsed() >= self.replay_interval);
+        if !state.queue.is_empty() && replay_due {
+            let flushed = self.replay(&mut state)?;
+            state.rejected.extend(flushed.rejected);
+        }
+        if state.queue.is_empty() {
+            match self.client.send_message(message.clone()) {
+                Ok(sent) => return Ok(Delivery::Sent(sent)),
+                Err(e) if !is_unreachable(&e) => return Err(e),
+                Err(_) => {}
+            }
+        }
+        let queued = Queued {
+            seq: state.next_seq,
+            app_token: self.client.gotify.app_token.clone(),
+            message,
+        };
+        append(
+            &mut state.log,
+            &Record::Queued {
+                seq: queued.seq,
+                app_token: queued.app_token.clone(),
+                message: queued.message.clone(),
+            },
+        )?;
+        state.next_seq += 1;
+        state.queue.push_back(queued);
+        self.depth.store(state.queue.len(), Ordering::Relaxed);
+        Ok(Delivery::Queued)
+    }
+
+    /// Replays queued messages in order if the server reports itself
+    /// healthy, stopping early if it becomes unreachable again. Messages
+    /// rejected during an earlier replay by `send` are reported here too.
+    pub fn flush(&self) -> Result<Flushed> {
+        let mut state = self.state();
+        let mut flushed = self.replay(&mut state)?;
+        let mut rejected = std::mem::take(&mut state.rejected);
+        rejected.append(&mut flushed.rejected);
+        flushed.rejected = rejected;
+        Ok(flushed)
+    }
+
+    fn replay(&self, state: &mut State) -> Result<Flushed> {
+        let mut flushed = Flushed::default();
+        if state.queue.is_empty() {
+            return Ok(flushed);
+        }
+        state.last_replay = Some(Instant::now());
+        match self.client.get_health() {
+            Ok(health) if health.health == "green" && health.database == "green" => {}
+            Ok(_) => return Ok(flushed),
+            Err(e) if is_unreachable(&e) => return Ok(flushed),
+            Err(e) => return Err(e),
+        }
+        while let Some(queued) = state.queue.front() {
+            let client = match &queued.app_token {
+                Some(token) => self.client.with_app_token(token.clone()),
+                None => self.client.clone(),
+            };
+            let result = client.send_message(queued.message.clone());
+            match result {
+                Err(e) if is_unreachable(&e) => break,
+                Ok(_) => flushed.sent += 1,
+                Err(e) => flushed.rejected.push((queued.message.clone(), e)),
+            }
+            let seq = queued.seq;
+            append(&mut state.log, &Record::Done { seq })?;
+            state.queue.pop_front();
+            self.depth.store(state.queue.len(), Ordering::Relaxed);
+        }
+        Ok(flushed)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        // The state stays consistent even if a holder panicked: the log is
+        // written before the queue changes.
+        lock(&self.state)
+    }
+}
+
+/// Whether an error means the server couldn't take the message right now,
+/// as opposed to refusing it.
+fn is_unreachable(error: &GotifyError) -> bool {
+    match error {
+        GotifyError::Transport(_) => true,
+        e => matches!(e.status(), Some(429 | 500..)),
+    }
+}
+
+fn read_log(path: &Path) -> Result<VecDeque<Queued>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(VecDeque::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut queue = VecDeque::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        // A crash mid-write can leave a truncated last line; skip it.
+        let Ok(record) = serde_json::from_str::<Record>(&line) else {
+            continue;
+        };
+        match record {
+            Record::Queued {
+                seq,
+                app_token,
+                message,
+            } => queue.push_back(Queued {
+                seq,
+                app_token,
+                message,
+            }),
+            Record::Done { seq } => queue.retain(|q| q.seq != seq),
+        }
+    }
+    Ok(queue)
+}
+
+/// Rewrites the log with only the pending messages and opens it for
+/// appending. It is replaced the same way as the token file, since it holds
+/// app tokens.
+fn compact(path: &Path, queue: &VecDeque<Queued>) -> Result<File> {
+    token_file::replace_private(path, |log| {
+        for queued in queue {
+            let record = Record::Queued {
+                seq: queued.seq,
+                app_token: queued.app_token.clone(),
+                message: queued.message.clone(),
+            };
+            writeln!(log, "{}", serde_json::to_string(&record)?)?;
+        }
+        Ok(())
+    })?;
+    Ok(OpenOptions::new().append(true).open(path)?)
+}
+
+fn append(log: &mut File, record: &Record) -> Result<()> {
+    writeln!(log, "{}", serde_json::to_string(record)?)?;
+    log.sync_data()?;
+    Ok(())
+}
@@ -0,0 +1,244 @@
+use std::collections::VecDeque;
+
+use crate::error::Result;
+use crate::ids::{AppId, MessageId};
+use crate::response_types::{Message, PagedMessages};
+use crate::SyncGotify;
+
+/// The bookkeeping shared by `MessagesIter` and `MessagesStream`.
+///
+/// Gotify returns messages newest first. Each page reports the id of its
+/// oldest message as `since`, which is passed back to fetch the next page.
+#[derive(Debug)]
+pub(crate) struct Pager {
+    app_id: Option<AppId>,
+    page_size: i32,
+    since: Option<MessageId>,
+    remaining: Option<usize>,
+    /// Stop at the first message sent before this, in Unix nanoseconds.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    cutoff: Option<i128>,
+    buffer: VecDeque<Message>,
+    exhausted: bool,
+}
+
+pub(crate) struct PageRequest {
+    pub(crate) app_id: Option<AppId>,
+    pub(crate) limit: Option<i32>,
+    pub(crate) since: Option<MessageId>,
+}
+
+impl Pager {
+    pub(crate) fn new(app_id: Option<AppId>, page_size: i32) -> Self {
+        Self {
+            app_id,
+            page_size,
+            since: None,
+            remaining: None,
+            #[cfg(any(feature = "chrono", feature = "time"))]
+            cutoff: None,
+            buffer: VecDeque::new(),
+            exhausted: false,
+        }
+    }
+
+    pub(crate) fn limit(&mut self, limit: usize) {
+        self.remaining = Some(limit);
+    }
+
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    pub(crate) fn until(&mut self, cutoff: &impl crate::timestamp::DateBound) {
+        self.cutoff = Some(cutoff.unix_nanos());
+    }
+
+    /// The next page to fetch, if the buffer is drained and there is more to read.
+    pub(crate) fn next_request(&self) -> Option<PageRequest> {
+        if self.exhausted || !self.buffer.is_empty() || self.remaining == Some(0) {
+            return None;
+        }
+        let limit = match self.remaining {
+            Some(remaining) => self.page_size.min(remaining.try_into().unwrap_or(i32::MAX)),
+            None => self.page_size,
+        };
+        Some(PageRequest {
+            app_id: self.app_id,
+            limit: Some(limit),
+            since: self.since,
+        })
+    }
+
+    pub(crate) fn push_page(&mut self, page: PagedMessages) {
+        let last_page = page.messages.is_empty()
+            || page.paging.next.as_deref().unwrap_or_default().is_empty()
+            || page.paging.since.0 <= 0;
+        self.since = Some(page.paging.since);
+        self.buffer.extend(page.messages);
+        self.exhausted |= last_page;
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.exhausted = true;
+        self.buffer.clear();
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<Message> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let message = self.buffer.pop_front()?;
+        if self.is_before_cutoff(&message) {
+            self.finish();
+            return None;
+        }
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        Some(message)
+    }
+
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    fn is_before_cutoff(&self, message: &Message) -> bool {
+        self.cutoff
+            .is_some_and(|cutoff| crate::timestamp::unix_nanos(&message.date) < cutoff)
+    }
+
+    #[cfg(not(any(feature = "chrono", feature = "time")))]
+    fn is_before_cutoff(&self, _message: &Message) -> bool {
+        false
+    }
+}
+
+/// Iterates over every message, newest first, fetching pages as needed.
+///
+/// Created by `SyncGotify::messages_iter`. Iteration stops after the first error.
+pub struct MessagesIter {
+    client: SyncGotify,
+    pager: Pager,
+}
+
+impl MessagesIter {
+    pub(crate) fn new(client: SyncGotify, pager: Pager) -> Self {
+        Self { client, pager }
+    }
+
+    /// Stops after `limit` messages in total.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.pager.limit(limit);
+        self
+    }
+
+    /// Stops at the first message sent before `cutoff`, without fetching further pages.
+    #[cfg(any(feature = "chrono", feature = "time"))]
+    pub fn until(mut self, cutoff: impl crate::timestamp::DateBound) -> Self {
+        self.pager.until(&cutoff);
+        self
+    }
+}
+
+impl Iterator for MessagesIter {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(message) = self.pager.pop() {
+                return Some(Ok(message));
+            }
+            let request = self.pager.next_request()?;
+            match self
+                .client
+                .get_messages(request.app_id, request.limit, request.since)
+            {
+                Ok(page) => self.pager.push_page(page),
+                Err(e) => {
+                    self.pager.finish();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::stream::MessagesStream;
+
+#[cfg(feature = "async")]
+mod stream {
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+
+    use futures::future::BoxFuture;
+    use futures::{FutureExt, Stream};
+
+    use super::Pager;
+    use crate::error::Result;
+    use crate::response_types::{Message, PagedMessages};
+    use crate::AsyncGotify;
+
+    /// Streams every message, newest first, fetching pages as needed.
+    ///
+    /// Created by `AsyncGotify::messages_stream`. The stream ends after the first error.
+    pub struct MessagesStream {
+        client: AsyncGotify,
+        pager: Pager,
+        in_flight: Option<BoxFuture<'static, Result<PagedMessages>>>,
+    }
+
+    impl MessagesStream {
+        pub(crate) fn new(client: AsyncGotify, pager: Pager) -> Self {
+            Self {
+                client,
+                pager,
+                in_flight: None,
+            }
+        }
+
+        /// Stops after `limit` messages in total.
+        pub fn limit(mut self, limit: usize) -> Self {
+            self.pager.limit(limit);
+            self
+        }
+
+        /// Stops at the first message sent before `cutoff`, without fetching further pages.
+        #[cfg(any(feature = "chrono", feature = "time"))]
+        pub fn until(mut self, cutoff: impl crate::timestamp::DateBound) -> Self {
+            self.pager.until(&cutoff);
+            self
+        }
+    }
+
+    impl Stream for MessagesStream {
+        type Item = Result<Message>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.get_mut();
+            loop {
+                if let Some(in_flight) = &mut this.in_flight {
+                    let page = ready!(in_flight.poll_unpin(cx));
+                    this.in_flight = None;
+                    match page {
+                        Ok(page) => this.pager.push_page(page),
+                        Err(e) => {
+                            this.pager.finish();
+                            return Poll::Ready(Some(Err(e)));
+                        }
+                    }
+                }
+                if let Some(message) = this.pager.pop() {
+                    return Poll::Ready(Some(Ok(message)));
+                }
+                let Some(request) = this.pager.next_request() else {
+                    return Poll::Ready(None);
+                };
+                let client = this.client.clone();
+                this.in_flight = Some(
+                    async move {
+                        client
+                            .get_messages(request.app_id, request.limit, request.since)
+                            .await
+                    }
+                    .boxed(),
+                );
+            }
+        }
+    }
+}
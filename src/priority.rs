@@ -0,0 +1,139 @@
+//! Message priorities and how they map onto other severity scales.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A message priority from 0 to 10.
+///
+/// The named levels are where the Gotify Android app changes how it notifies:
+/// `MIN` shows nothing, `LOW` (1–3) is silent, `NORMAL` (4–7) plays a sound
+/// and `HIGH` (8–10) also pops up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "i32", into = "i32")]
+pub struct Priority(u8);
+
+/// A priority outside `0..=10`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("priority {0} is outside 0..=10")]
+pub struct InvalidPriority(pub i32);
+
+impl Priority {
+    pub const MIN: Priority = Priority(0);
+    pub const LOW: Priority = Priority(1);
+    pub const NORMAL: Priority = Priority(5);
+    pub const HIGH: Priority = Priority(8);
+    pub const URGENT: Priority = Priority(10);
+
+    pub fn new(priority: i32) -> Result<Self, InvalidPriority> {
+        match u8::try_from(priority) {
+            Ok(p) if p <= 10 => Ok(Priority(p)),
+            _ => Err(InvalidPriority(priority)),
+        }
+    }
+
+    /// Clamps any value into `0..=10`, e.g. for priorities read back from the
+    /// server, which doesn't limit them.
+    pub fn saturating(priority: i32) -> Self {
+        Priority(priority.clamp(0, 10) as u8)
+    }
+
+    pub fn get(self) -> i32 {
+        self.0.into()
+    }
+
+    pub fn to_syslog(self) -> SyslogSeverity {
+        match self.0 {
+            10 => SyslogSeverity::Alert,
+            9 => SyslogSeverity::Critical,
+            8 => SyslogSeverity::Error,
+            4..=7 => SyslogSeverity::Warning,
+            2..=3 => SyslogSeverity::Notice,
+            1 => SyslogSeverity::Informational,
+            _ => SyslogSeverity::Debug,
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl TryFrom<i32> for Priority {
+    type Error = InvalidPriority;
+
+    fn try_from(priority: i32) -> Result<Self, InvalidPriority> {
+        Priority::new(priority)
+    }
+}
+
+impl From<Priority> for i32 {
+    fn from(priority: Priority) -> i32 {
+        priority.get()
+    }
+}
+
+/// Syslog severities (RFC 5424), most severe first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SyslogSeverity {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
+    Warning = 4,
+    Notice = 5,
+    Informational = 6,
+    Debug = 7,
+}
+
+impl SyslogSeverity {
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+impl From<SyslogSeverity> for Priority {
+    fn from(severity: SyslogSeverity) -> Self {
+        match severity {
+            SyslogSeverity::Emergency | SyslogSeverity::Alert => Priority::URGENT,
+            SyslogSeverity::Critical => Priority(9),
+            SyslogSeverity::Error => Priority::HIGH,
+            SyslogSeverity::Warning => Priority::NORMAL,
+            SyslogSeverity::Notice => Priority(3),
+            SyslogSeverity::Informational => Priority::LOW,
+            SyslogSeverity::Debug => Priority::MIN,
+        }
+    }
+}
+
+impl From<Priority> for SyslogSeverity {
+    fn from(priority: Priority) -> Self {
+        priority.to_syslog()
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<log::Level> for Priority {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Error => Priority::HIGH,
+            log::Level::Warn => Priority::NORMAL,
+            log::Level::Info => Priority::LOW,
+            log::Level::Debug | log::Level::Trace => Priority::MIN,
+        }
+    }
+}
+
+#[cfg(feature = "log")]
+impl From<Priority> for log::Level {
+    fn from(priority: Priority) -> Self {
+        match priority.0 {
+            8..=10 => log::Level::Error,
+            4..=7 => log::Level::Warn,
+            1..=3 => log::Level::Info,
+            _ => log::Level::Debug,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+//! Request bodies and query strings, built by the functions in `endpoints`.
+
+use serde::Serialize;
+
+use crate::ids::MessageId;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ApplicationParams {
+    pub(crate) name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ClientParams {
+    pub(crate) name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CreateUser {
+    pub(crate) name: String,
+    pub(crate) pass: String,
+    pub(crate) admin: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SetPassword {
+    pub(crate) pass: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UpdateUser {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) pass: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) admin: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct MessagesQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) limit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) since: Option<MessageId>,
+}
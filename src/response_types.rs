@@ -1,102 +1,181 @@
+//! Types returned by the Gotify API.
+//!
+//! They are `#[non_exhaustive]` and keep any field this crate doesn't know in
+//! `extra`, so newer servers can add fields without breaking deserialization.
+
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::ids::*;
+use crate::timestamp::Timestamp;
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Application {
     pub description: String,
-    pub id: i32,
+    pub id: AppId,
     pub image: String,
     pub internal: bool,
     pub name: String,
     pub token: String,
+    /// The priority used for messages sent without one; older servers don't
+    /// report it.
+    #[serde(
+        rename = "defaultPriority",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub default_priority: Option<i32>,
+    /// When the token was last used, as an RFC 3339 timestamp.
+    #[serde(
+        rename = "lastUsed",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::timestamp::rfc3339::option"
+    )]
+    pub last_used: Option<Timestamp>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Client {
-    pub id: i32,
+    pub id: ClientId,
     pub name: String,
     pub token: String,
+    #[serde(
+        rename = "lastUsed",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::timestamp::rfc3339::option"
+    )]
+    pub last_used: Option<Timestamp>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Error {
     pub error: String,
     #[serde(rename = "errorCode")]
     pub code: i32,
     #[serde(rename = "errorDescription")]
     pub description: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Health {
     pub database: String,
     pub health: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Message {
-    pub appid: i32,
-    pub date: String,
-    pub extras: HashMap<String, serde_json::Value>,
-    pub id: i32,
+    pub appid: AppId,
+    #[serde(with = "crate::timestamp::rfc3339")]
+    pub date: Timestamp,
+    /// Gotify omits `extras` when there are none.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub extras: HashMap<String, Value>,
+    pub id: MessageId,
     pub message: String,
+    #[serde(default)]
     pub priority: i32,
-    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct PagedMessages {
     pub messages: Vec<Message>,
     pub paging: Paging,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct Paging {
     pub limit: i32,
-    pub next: String,
-    pub since: i32,
+    /// The URL of the next page; Gotify leaves it out on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    pub since: MessageId,
     pub size: i32,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct PluginConf {
-    pub author: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(default)]
     pub capabilities: Vec<String>,
     pub enabled: bool,
-    pub id: i32,
-    pub license: String,
+    pub id: PluginId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
     #[serde(rename = "modulePath")]
     pub module_path: String,
     pub name: String,
     pub token: String,
-    pub website: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub website: Option<String>,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct User {
     pub admin: bool,
-    pub id: i32,
+    pub id: UserId,
     pub name: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct UserPass {
     pub pass: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct UserWithPass {
     pub admin: bool,
-    pub id: i32,
+    pub id: UserId,
     pub name: String,
     pub pass: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
+#[non_exhaustive]
 pub struct VersionInfo {
     #[serde(rename = "buildDate")]
     pub build_date: String,
     pub commit: String,
     pub version: String,
+    #[serde(flatten)]
+    pub extra: HashMap<String, Value>,
 }
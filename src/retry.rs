@@ -0,0 +1,149 @@
+//! Retrying failed requests.
+
+use std::time::{Duration, SystemTime};
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::{Method, StatusCode};
+
+use crate::message::NewMessage;
+
+/// The extras namespace holding the dedup key added by
+/// `MessageRetry::WithDedupKey`, e.g. `{"gotify_rs::dedup": {"key": "…"}}`.
+pub const DEDUP_EXTRA: &str = "gotify_rs::dedup";
+
+/// When and how often a failed request is sent again.
+///
+/// GET and DELETE requests are retried after transport errors and on the
+/// statuses in `retry_statuses`. Sending a message isn't idempotent, so it is
+/// only retried as configured by `messages`. Other requests are never retried.
+/// Clients use the default policy unless built with another one.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts in total, including the first; 1 disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: u32,
+    /// Wait a random time between half the backoff and the full backoff.
+    pub jitter: bool,
+    pub retry_statuses: Vec<u16>,
+    /// Waits as long as a `Retry-After` header asks, unless that is longer
+    /// than `max_backoff`, in which case the request fails instead.
+    pub honor_retry_after: bool,
+    pub messages: MessageRetry,
+}
+
+/// Whether `create_message` and `send_message` are retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageRetry {
+    #[default]
+    Never,
+    /// Retry; a message that reached the server before a failure is sent twice.
+    Retry,
+    /// Retry, marking every attempt with the same random key in the
+    /// `DEDUP_EXTRA` extras so the receiving side can drop duplicates.
+    WithDedupKey,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2,
+            jitter: true,
+            retry_statuses: vec![429, 502, 503, 504],
+            honor_retry_after: true,
+            messages: MessageRetry::Never,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Sends every request exactly once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// How long to wait before sending a request again after attempt number
+    /// `attempt` (starting at 1) ended in `outcome`, or `None` to stop.
+    pub(crate) fn delay(
+        &self,
+        method: &Method,
+        retry_message: bool,
+        attempt: u32,
+        outcome: Outcome<'_>,
+    ) -> Option<Duration> {
+        let retryable_call = match *method {
+            Method::GET | Method::DELETE => true,
+            Method::POST => retry_message && self.messages != MessageRetry::Never,
+            _ => false,
+        };
+        if !retryable_call || attempt >= self.max_attempts {
+            return None;
+        }
+        let retry_after = match outcome {
+            Outcome::Status(status, _) if !self.retry_statuses.contains(&status.as_u16()) => {
+                return None
+            }
+            Outcome::Status(_, headers) if self.honor_retry_after => retry_after(headers),
+            Outcome::Status(..) | Outcome::Transport => None,
+        };
+        match retry_after {
+            Some(wait) if wait > self.max_backoff => None,
+            Some(wait) => Some(wait),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    /// Tags the message with a dedup key if this policy asks for one and the
+    /// caller hasn't set one already.
+    pub(crate) fn prepare_message(&self, message: &mut NewMessage) {
+        if self.messages == MessageRetry::WithDedupKey {
+            message
+                .extras
+                .entry(DEDUP_EXTRA.to_string())
+                .or_insert_with(|| serde_json::json!({ "key": dedup_key() }));
+        }
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(self.multiplier.saturating_pow(attempt - 1))
+            .min(self.max_backoff);
+        if self.jitter {
+            rand::thread_rng().gen_range(backoff / 2..=backoff)
+        } else {
+            backoff
+        }
+    }
+}
+
+/// How an attempt failed.
+pub(crate) enum Outcome<'a> {
+    Status(StatusCode, &'a HeaderMap),
+    Transport,
+}
+
+/// Parses `Retry-After` as either delay-seconds or an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+fn dedup_key() -> String {
+    format!("{:032x}", rand::thread_rng().gen::<u128>())
+}
@@ -0,0 +1,459 @@
+//! Sending messages from a background task, off the caller's request path.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::GotifyError;
+use crate::message::NewMessage;
+use crate::sync::{lock, ErrorHandler};
+use crate::SyncGotify;
+
+/// What `send` does when the queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Wait for room.
+    #[default]
+    Block,
+    /// Drop the oldest queued message to make room.
+    DropOldest,
+    /// Return `SendError::Full`.
+    Error,
+}
+
+/// A message that couldn't be queued, handed back to the caller.
+#[derive(Debug, thiserror::Error)]
+pub enum SendError {
+    #[error("the sender's queue is full")]
+    Full(NewMessage),
+    #[error("the sender has been shut down")]
+    Closed(NewMessage),
+}
+
+impl SendError {
+    pub fn into_message(self) -> NewMessage {
+        match self {
+            SendError::Full(message) | SendError::Closed(message) => message,
+        }
+    }
+}
+
+/// Configures a `Sender` or `BlockingSender`.
+pub struct SenderBuilder {
+    capacity: usize,
+    overflow: Overflow,
+    on_error: Option<ErrorHandler>,
+}
+
+impl Default for SenderBuilder {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            overflow: Overflow::default(),
+            on_error: None,
+        }
+    }
+}
+
+impl SenderBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many messages may wait to be sent. Defaults to 1024.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Called with each message the server didn't accept. Without it, failed
+    /// messages are dropped.
+    pub fn on_error(
+        mut self,
+        on_error: impl Fn(NewMessage, GotifyError) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    /// Starts a tokio task sending through `client`. Must be called within a
+    /// tokio runtime.
+    #[cfg(feature = "async")]
+    pub fn spawn(self, client: crate::AsyncGotify) -> Sender {
+        Sender::spawn(self, client)
+    }
+
+    /// Starts a thread sending through `client`.
+    pub fn spawn_blocking(self, client: SyncGotify) -> BlockingSender {
+        BlockingSender::spawn(self, client)
+    }
+
+    fn queue(self) -> Queue {
+        Queue {
+            state: Mutex::new(State::default()),
+            capacity: self.capacity,
+            overflow: self.overflow,
+            on_error: self.on_error,
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<NewMessage>,
+    closed: bool,
+    /// Set once the worker has sent everything and exited.
+    finished: bool,
+}
+
+/// The queue shared by a sender's handles and its worker.
+struct Queue {
+    state: Mutex<State>,
+    capacity: usize,
+    overflow: Overflow,
+    on_error: Option<ErrorHandler>,
+}
+
+enum Push {
+    Queued,
+    /// The queue is full and the overflow policy is to wait.
+    Wait(NewMessage),
+}
+
+impl Queue {
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn push(&self, state: &mut State, message: NewMessage) -> Result<Push, SendError> {
+        if state.closed {
+            return Err(SendError::Closed(message));
+        }
+        if state.queue.len() >= self.capacity {
+            match self.overflow {
+                Overflow::Block => return Ok(Push::Wait(message)),
+                Overflow::DropOldest => {
+                    state.queue.pop_front();
+                }
+                Overflow::Error => return Err(SendError::Full(message)),
+            }
+        }
+        state.queue.push_back(message);
+        Ok(Push::Queued)
+    }
+
+    fn report(&self, message: NewMessage, error: GotifyError) {
+        if let Some(on_error) = &self.on_error {
+            on_error(message, error);
+        }
+    }
+
+    /// Closes the queue and takes whatever the worker hasn't started on.
+    fn abandon(&self) -> Vec<NewMessage> {
+        let mut state = self.state();
+        state.closed = true;
+        state.queue.drain(..).collect()
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::task::Sender;
+
+#[cfg(feature = "async")]
+mod task {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tokio::sync::Notify;
+
+    use super::{Push, Queue, SendError, SenderBuilder};
+    use crate::message::NewMessage;
+    use crate::AsyncGotify;
+
+    /// Queues messages for a background task that sends them through an
+    /// `AsyncGotify`, so callers don't wait on the network.
+    ///
+    /// Created by `SenderBuilder::spawn`. Clones share the same queue and
+    /// task. Dropping the last clone without `shutdown` closes the queue; the
+    /// task then sends what is left and exits.
+    #[derive(Clone)]
+    pub struct Sender {
+        inner: Arc<Inner>,
+    }
+
+    /// Owned by the handles only, so it drops with the last of them.
+    struct Inner {
+        shared: Arc<Shared>,
+    }
+
+    impl Drop for Inner {
+        fn drop(&mut self) {
+            self.shared.close();
+        }
+    }
+
+    struct Shared {
+        queue: Queue,
+        item_ready: Notify,
+        space_ready: Notify,
+        /// Notified once the task has sent everything and exited.
+        finished: Notify,
+    }
+
+    impl Shared {
+        fn close(&self) {
+            self.queue.state().closed = true;
+            self.item_ready.notify_one();
+            self.space_ready.notify_waiters();
+        }
+    }
+
+    impl Sender {
+        pub fn builder() -> SenderBuilder {
+            SenderBuilder::new()
+        }
+
+        pub(super) fn spawn(builder: SenderBuilder, client: AsyncGotify) -> Self {
+            let shared = Arc::new(Shared {
+                queue: builder.queue(),
+                item_ready: Notify::new(),
+                space_ready: Notify::new(),
+                finished: Notify::new(),
+            });
+            tokio::spawn(work(shared.clone(), client));
+            Self {
+                inner: Arc::new(Inner { shared }),
+            }
+        }
+
+        /// Queues a message, waiting for room if the queue is full and the
+        /// overflow policy is `Overflow::Block`.
+        pub async fn send(&self, message: impl Into<NewMessage>) -> Result<(), SendError> {
+            let shared = &self.inner.shared;
+            let mut message = message.into();
+            loop {
+                let space_ready = shared.space_ready.notified();
+                let push = {
+                    let mut state = shared.queue.state();
+                    shared.queue.push(&mut state, message)?
+                };
+                match push {
+                    Push::Queued => {
+                        shared.item_ready.notify_one();
+                        return Ok(());
+                    }
+                    Push::Wait(waiting) => {
+                        message = waiting;
+                        space_ready.await;
+                    }
+                }
+            }
+        }
+
+        /// Queues a message without waiting; a full queue is an error even
+        /// with `Overflow::Block`.
+        pub fn try_send(&self, message: impl Into<NewMessage>) -> Result<(), SendError> {
+            let shared = &self.inner.shared;
+            let mut state = shared.queue.state();
+            match shared.queue.push(&mut state, message.into())? {
+                Push::Queued => {
+                    shared.item_ready.notify_one();
+                    Ok(())
+                }
+                Push::Wait(message) => Err(SendError::Full(message)),
+            }
+        }
+
+        /// The number of messages waiting to be sent.
+        pub fn pending(&self) -> usize {
+            self.inner.shared.queue.state().queue.len()
+        }
+
+        /// Stops accepting messages and waits up to `deadline` for the queued
+        /// ones to be sent, returning those that weren't. A message already
+        /// being sent at the deadline is left to finish in the background.
+        /// Clones shutting down at once each wait up to their own deadline.
+        pub async fn shutdown(&self, deadline: Duration) -> Vec<NewMessage> {
+            let shared = &self.inner.shared;
+            shared.close();
+            let finished = async {
+                loop {
+                    let finished = shared.finished.notified();
+                    if shared.queue.state().finished {
+                        return;
+                    }
+                    finished.await;
+                }
+            };
+            let _ = tokio::time::timeout(deadline, finished).await;
+            shared.queue.abandon()
+        }
+    }
+
+    async fn work(shared: Arc<Shared>, client: AsyncGotify) {
+        loop {
+            let item_ready = shared.item_ready.notified();
+            let next = {
+                let mut state = shared.queue.state();
+                let next = state.queue.pop_front();
+                if next.is_none() && state.closed {
+                    state.finished = true;
+                    shared.finished.notify_waiters();
+                    return;
+                }
+                next
+            };
+            let Some(message) = next else {
+                item_ready.await;
+                continue;
+            };
+            shared.space_ready.notify_one();
+            if let Err(e) = client.send_message(message.clone()).await {
+                shared.queue.report(message, e);
+            }
+        }
+    }
+}
+
+/// Queues messages for a background thread that sends them through a
+/// `SyncGotify`, so callers don't wait on the network.
+///
+/// Created by `SenderBuilder::spawn_blocking`. Clones share the same queue
+/// and thread. Dropping the last clone without `shutdown` closes the queue;
+/// the thread then sends what is left and exits.
+#[derive(Clone)]
+pub struct BlockingSender {
+    inner: Arc<BlockingInner>,
+}
+
+/// Owned by the handles only, so it drops with the last of them.
+struct BlockingInner {
+    shared: Arc<BlockingShared>,
+}
+
+impl Drop for BlockingInner {
+    fn drop(&mut self) {
+        self.shared.queue.state().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+struct BlockingShared {
+    queue: Queue,
+    changed: Condvar,
+}
+
+impl BlockingSender {
+    pub fn builder() -> SenderBuilder {
+        SenderBuilder::new()
+    }
+
+    fn spawn(builder: SenderBuilder, client: SyncGotify) -> Self {
+        let shared = Arc::new(BlockingShared {
+            queue: builder.queue(),
+            changed: Condvar::new(),
+        });
+        let worker = shared.clone();
+        std::thread::spawn(move || work_blocking(&worker, &client));
+        Self {
+            inner: Arc::new(BlockingInner { shared }),
+        }
+    }
+
+    /// Queues a message, blocking for room if the queue is full and the
+    /// overflow policy is `Overflow::Block`.
+    pub fn send(&self, message: impl Into<NewMessage>) -> Result<(), SendError> {
+        let mut message = message.into();
+        let mut state = self.inner.shared.queue.state();
+        loop {
+            match self.inner.shared.queue.push(&mut state, message)? {
+                Push::Queued => {
+                    self.inner.shared.changed.notify_all();
+                    return Ok(());
+                }
+                Push::Wait(waiting) => {
+                    message = waiting;
+                    state = self
+                        .inner
+                        .shared
+                        .changed
+                        .wait(state)
+                        .unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+    }
+
+    /// Queues a message without blocking; a full queue is an error even with
+    /// `Overflow::Block`.
+    pub fn try_send(&self, message: impl Into<NewMessage>) -> Result<(), SendError> {
+        let mut state = self.inner.shared.queue.state();
+        match self.inner.shared.queue.push(&mut state, message.into())? {
+            Push::Queued => {
+                self.inner.shared.changed.notify_all();
+                Ok(())
+            }
+            Push::Wait(message) => Err(SendError::Full(message)),
+        }
+    }
+
+    /// The number of messages waiting to be sent.
+    pub fn pending(&self) -> usize {
+        self.inner.shared.queue.state().queue.len()
+    }
+
+    /// Stops accepting messages and waits up to `deadline` for the queued
+    /// ones to be sent, returning those that weren't. A message already
+    /// being sent at the deadline is left to finish in the background.
+    pub fn shutdown(&self, deadline: Duration) -> Vec<NewMessage> {
+        let until = Instant::now() + deadline;
+        let mut state = self.inner.shared.queue.state();
+        state.closed = true;
+        self.inner.shared.changed.notify_all();
+        while !state.finished {
+            let Some(left) = until.checked_duration_since(Instant::now()) else {
+                break;
+            };
+            state = self
+                .inner
+                .shared
+                .changed
+                .wait_timeout(state, left)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        drop(state);
+        self.inner.shared.queue.abandon()
+    }
+}
+
+fn work_blocking(shared: &BlockingShared, client: &SyncGotify) {
+    loop {
+        let message = {
+            let mut state = shared.queue.state();
+            loop {
+                if let Some(message) = state.queue.pop_front() {
+                    break message;
+                }
+                if state.closed {
+                    state.finished = true;
+                    shared.changed.notify_all();
+                    return;
+                }
+                state = shared
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner());
+            }
+        };
+        shared.changed.notify_all();
+        if let Err(e) = client.send_message(message.clone()) {
+            shared.queue.report(message, e);
+        }
+    }
+}
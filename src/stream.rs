@@ -0,0 +1,272 @@
+//! Live delivery of new messages over Gotify's `/stream` WebSocket.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use tungstenite::client::IntoClientRequest;
+use tungstenite::handshake::client::Request;
+use tungstenite::http::HeaderValue;
+use tungstenite::Message as Frame;
+
+use crate::endpoints::STREAM;
+use crate::error::{GotifyError, Result};
+use crate::response_types::Message;
+use crate::{Credentials, Gotify};
+
+impl Gotify {
+    /// The WebSocket handshake request for `/stream`, authenticated like any
+    /// other client-token endpoint.
+    pub(crate) fn stream_request(&self) -> Result<Request> {
+        let mut url = self.url(STREAM.info.path)?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme)
+            .expect("http(s) URLs can always be switched to ws(s)");
+        let mut request = url.as_str().into_client_request()?;
+        let header = match self.credentials(&STREAM.info.auth)? {
+            Credentials::Token(token) => Some(("X-Gotify-Key", token.to_string())),
+            Credentials::Basic(user, pass) => Some((
+                "Authorization",
+                format!("Basic {}", BASE64.encode(format!("{user}:{pass}"))),
+            )),
+            Credentials::None => None,
+        };
+        if let Some((name, value)) = header {
+            let value = HeaderValue::from_str(&value)
+                .map_err(|e| tungstenite::Error::HttpFormat(e.into()))?;
+            request.headers_mut().insert(name, value);
+        }
+        Ok(request)
+    }
+}
+
+/// Decodes a data frame into a `Message`; control frames yield `None`.
+pub(crate) fn decode_frame(frame: Frame) -> Option<Result<Message>> {
+    match frame {
+        Frame::Text(text) => Some(serde_json::from_str(&text).map_err(GotifyError::from)),
+        Frame::Binary(bytes) => Some(serde_json::from_slice(&bytes).map_err(GotifyError::from)),
+        Frame::Ping(_) | Frame::Pong(_) | Frame::Close(_) | Frame::Frame(_) => None,
+    }
+}
+
+/// Whether a WebSocket error just means the connection has been closed.
+pub(crate) fn is_closed(error: &tungstenite::Error) -> bool {
+    matches!(
+        error,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed
+    )
+}
+
+pub use self::blocking::{BlockingMessageStream, StreamCloser};
+#[cfg(feature = "async")]
+pub use self::live::MessageStream;
+
+mod blocking {
+    use std::io;
+    use std::net::{Shutdown, TcpStream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use tungstenite::stream::MaybeTlsStream;
+    use tungstenite::WebSocket;
+
+    use super::{decode_frame, is_closed};
+    use crate::error::{GotifyError, Result};
+    use crate::response_types::Message;
+    use crate::Gotify;
+
+    /// Messages pushed by the server as they are created, read on the calling thread.
+    ///
+    /// Created by `SyncGotify::stream`. Pings are answered automatically and
+    /// iteration ends when the server closes the connection or the stream is
+    /// closed through a `StreamCloser`.
+    pub struct BlockingMessageStream {
+        socket: WebSocket<MaybeTlsStream<TcpStream>>,
+        closer: StreamCloser,
+        done: bool,
+    }
+
+    /// Closes a `BlockingMessageStream` from another thread.
+    ///
+    /// A pending read wakes up and iteration ends without an error. The
+    /// connection is dropped rather than closed with a handshake, since the
+    /// close frame can't be sent while another thread is reading.
+    #[derive(Debug, Clone)]
+    pub struct StreamCloser {
+        tcp: Arc<TcpStream>,
+        closed: Arc<AtomicBool>,
+    }
+
+    impl StreamCloser {
+        pub fn close(&self) {
+            self.closed.store(true, Ordering::SeqCst);
+            let _ = self.tcp.shutdown(Shutdown::Both);
+        }
+
+        pub fn is_closed(&self) -> bool {
+            self.closed.load(Ordering::SeqCst)
+        }
+    }
+
+    fn tcp(stream: &MaybeTlsStream<TcpStream>) -> &TcpStream {
+        match stream {
+            MaybeTlsStream::Plain(tcp) => tcp,
+            MaybeTlsStream::NativeTls(tls) => tls.get_ref(),
+            _ => unreachable!("only native-tls connections are made"),
+        }
+    }
+
+    fn read_error(error: tungstenite::Error) -> GotifyError {
+        match error {
+            tungstenite::Error::Io(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                GotifyError::Timeout
+            }
+            e => e.into(),
+        }
+    }
+
+    impl BlockingMessageStream {
+        pub(crate) fn connect(gotify: &Gotify) -> Result<Self> {
+            let (socket, _) = tungstenite::connect(gotify.stream_request()?)?;
+            let closer = StreamCloser {
+                tcp: Arc::new(tcp(socket.get_ref()).try_clone()?),
+                closed: Arc::new(AtomicBool::new(false)),
+            };
+            Ok(Self {
+                socket,
+                closer,
+                done: false,
+            })
+        }
+
+        /// Makes a read that waits longer than `timeout` yield
+        /// `GotifyError::Timeout`; iteration can carry on afterwards.
+        /// `None` waits indefinitely, which is the default.
+        pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+            tcp(self.socket.get_ref()).set_read_timeout(timeout)?;
+            Ok(())
+        }
+
+        /// A handle for closing this stream from another thread.
+        pub fn closer(&self) -> StreamCloser {
+            self.closer.clone()
+        }
+
+        /// Sends a close frame and waits for the server to acknowledge it.
+        pub fn close(&mut self) -> Result<()> {
+            self.done = true;
+            match self.socket.close(None) {
+                Err(e) if !is_closed(&e) => return Err(e.into()),
+                _ => {}
+            }
+            loop {
+                match self.socket.read() {
+                    Ok(_) => {}
+                    Err(e) if is_closed(&e) => return Ok(()),
+                    Err(e) => return Err(read_error(e)),
+                }
+            }
+        }
+    }
+
+    impl Iterator for BlockingMessageStream {
+        type Item = Result<Message>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            while !self.done {
+                if self.closer.is_closed() {
+                    break;
+                }
+                match self.socket.read() {
+                    Ok(frame) => {
+                        if let Some(message) = decode_frame(frame) {
+                            return Some(message);
+                        }
+                    }
+                    Err(e) if is_closed(&e) => self.done = true,
+                    // Reads fail once the closer shuts the socket down.
+                    Err(_) if self.closer.is_closed() => {}
+                    Err(e) => {
+                        let error = read_error(e);
+                        if !matches!(error, GotifyError::Timeout) {
+                            self.done = true;
+                        }
+                        return Some(Err(error));
+                    }
+                }
+            }
+            None
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+mod live {
+    use std::pin::Pin;
+    use std::task::{ready, Context, Poll};
+
+    use futures::{Stream, StreamExt};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+    use super::{decode_frame, is_closed};
+    use crate::error::Result;
+    use crate::response_types::Message;
+    use crate::Gotify;
+
+    /// Messages pushed by the server as they are created.
+    ///
+    /// Created by `AsyncGotify::stream`. Pings are answered automatically and
+    /// the stream ends when the server closes the connection.
+    pub struct MessageStream {
+        socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    }
+
+    impl MessageStream {
+        pub(crate) async fn connect(gotify: &Gotify) -> Result<Self> {
+            let (socket, _) = tokio_tungstenite::connect_async(gotify.stream_request()?).await?;
+            Ok(Self { socket })
+        }
+
+        /// Sends a close frame and waits for the server to acknowledge it.
+        pub async fn close(&mut self) -> Result<()> {
+            match self.socket.close(None).await {
+                Err(e) if !is_closed(&e) => return Err(e.into()),
+                _ => {}
+            }
+            while let Some(frame) = self.socket.next().await {
+                if let Err(e) = frame {
+                    if !is_closed(&e) {
+                        return Err(e.into());
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl Stream for MessageStream {
+        type Item = Result<Message>;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            loop {
+                // A close frame is answered on the next read, which then reports
+                // the connection as closed, so keep reading until that happens.
+                match ready!(self.socket.poll_next_unpin(cx)) {
+                    None => return Poll::Ready(None),
+                    Some(Err(e)) if is_closed(&e) => return Poll::Ready(None),
+                    Some(Err(e)) => return Poll::Ready(Some(Err(e.into()))),
+                    Some(Ok(frame)) => {
+                        if let Some(message) = decode_frame(frame) {
+                            return Poll::Ready(Some(message));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::stream::BoxStream;
+use futures::{Stream, StreamExt};
+
+use crate::error::{GotifyError, Result};
+use crate::ids::MessageId;
+use crate::response_types::Message;
+use crate::stream::MessageStream;
+use crate::AsyncGotify;
+
+/// How `Subscription` waits between reconnection attempts.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// The shortest wait before reconnecting. It grows with each failed
+    /// attempt in a row, up to `max_backoff`.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: u32,
+    /// Give up after this many failed attempts in a row; `None` retries forever.
+    pub max_attempts: Option<u32>,
+    /// Page size used to fetch messages missed while disconnected.
+    pub backfill_page_size: i32,
+    /// How long a connection must stay up, if it delivers nothing, before a
+    /// drop no longer counts as a failed attempt.
+    pub stable_after: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            multiplier: 2,
+            max_attempts: None,
+            backfill_page_size: 100,
+            stable_after: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    fn backoff(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+        let factor = self.multiplier.saturating_pow(failures - 1);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// A live message stream that survives disconnects.
+///
+/// Created by `AsyncGotify::subscribe`. After reconnecting it first delivers,
+/// oldest first, every message created since the last one it yielded, then
+/// resumes live delivery, skipping anything it has already seen. The stream
+/// only ends with an error: when credentials are rejected, or when
+/// `ReconnectPolicy::max_attempts` is exhausted.
+pub struct Subscription {
+    inner: BoxStream<'static, Result<Message>>,
+}
+
+impl Subscription {
+    pub(crate) fn new(
+        client: AsyncGotify,
+        last_id: Option<MessageId>,
+        policy: ReconnectPolicy,
+    ) -> Self {
+        let state = State {
+            client,
+            policy,
+            last_id,
+            live: None,
+            connected_at: None,
+            backlog: VecDeque::new(),
+            failures: 0,
+            done: false,
+        };
+        let inner = futures::stream::unfold(state, |mut state| async move {
+            let item = state.next().await?;
+            Some((item, state))
+        })
+        .boxed();
+        Self { inner }
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+struct State {
+    client: AsyncGotify,
+    policy: ReconnectPolicy,
+    last_id: Option<MessageId>,
+    live: Option<MessageStream>,
+    /// When the last connection was made; `None` before the first one.
+    connected_at: Option<Instant>,
+    backlog: VecDeque<Message>,
+    failures: u32,
+    done: bool,
+}
+
+impl State {
+    async fn next(&mut self) -> Option<Result<Message>> {
+        if self.done {
+            return None;
+        }
+        loop {
+            if let Some(message) = self.backlog.pop_front() {
+                if let Some(message) = self.accept(message) {
+                    return Some(Ok(message));
+                }
+                continue;
+            }
+            let Some(live) = &mut self.live else {
+                if let Err(e) = self.reconnect().await {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+                continue;
+            };
+            match live.next().await {
+                Some(Ok(message)) => {
+                    self.failures = 0;
+                    if let Some(message) = self.accept(message) {
+                        return Some(Ok(message));
+                    }
+                }
+                // A frame that isn't a message doesn't break the connection.
+                Some(Err(e @ GotifyError::Decode(_))) => {
+                    self.failures = 0;
+                    return Some(Err(e));
+                }
+                Some(Err(_)) | None => self.dropped(),
+            }
+        }
+    }
+
+    /// Passes on messages newer than the last one delivered.
+    fn accept(&mut self, message: Message) -> Option<Message> {
+        if self.last_id.is_some_and(|last_id| message.id <= last_id) {
+            return None;
+        }
+        self.last_id = Some(message.id);
+        Some(message)
+    }
+
+    /// A connection that drops before delivering anything or staying up for
+    /// `stable_after` counts as a failed attempt, so a server that accepts the
+    /// handshake and hangs up straight away is retried with growing backoff.
+    fn dropped(&mut self) {
+        self.live = None;
+        let stable = self
+            .connected_at
+            .is_some_and(|at| at.elapsed() >= self.policy.stable_after);
+        if stable {
+            self.failures = 0;
+        } else {
+            self.failures += 1;
+        }
+    }
+
+    /// Reconnects with backoff, returning only for errors that retrying can't fix.
+    async fn reconnect(&mut self) -> Result<()> {
+        loop {
+            let mut backoff = self.policy.backoff(self.failures);
+            if self.connected_at.is_some() {
+                // Only the very first connection is made without waiting.
+                backoff = backoff.max(self.policy.initial_backoff);
+            }
+            tokio::time::sleep(backoff).await;
+            match self.connect().await {
+                Ok(()) => {
+                    self.connected_at = Some(Instant::now());
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.failures += 1;
+                    let exhausted = self
+                        .policy
+                        .max_attempts
+                        .is_some_and(|max| self.failures >= max);
+                    if exhausted || is_fatal(&e) {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn connect(&mut self) -> Result<()> {
+        // Subscribe before backfilling so nothing created in between is missed;
+        // anything seen twice is dropped by `accept`.
+        let live = self.client.stream().await?;
+        if let Some(last_id) = self.last_id {
+            self.backlog = self.missed_since(last_id).await?;
+        }
+        self.live = Some(live);
+        Ok(())
+    }
+
+    /// Messages newer than `last_id`, oldest first.
+    async fn missed_since(&self, last_id: MessageId) -> Result<VecDeque<Message>> {
+        let mut pages = self
+            .client
+            .messages_stream(None, self.policy.backfill_page_size);
+        let mut missed = VecDeque::new();
+        while let Some(message) = pages.next().await {
+            let message = message?;
+            if message.id <= last_id {
+                break;
+            }
+            missed.push_front(message);
+        }
+        Ok(missed)
+    }
+}
+
+fn is_fatal(error: &GotifyError) -> bool {
+    matches!(
+        error,
+        GotifyError::MissingCredentials(_) | GotifyError::InvalidUrl(_)
+    ) || matches!(error.status(), Some(401 | 403))
+}
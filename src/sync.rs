@@ -0,0 +1,15 @@
+//! Pieces shared by the background senders.
+
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::error::GotifyError;
+use crate::message::NewMessage;
+
+/// Called with a message that couldn't be sent and why.
+pub(crate) type ErrorHandler = Arc<dyn Fn(NewMessage, GotifyError) + Send + Sync>;
+
+/// Locks `mutex`, carrying on if a holder panicked. Callers keep their state
+/// consistent at every point a panic could happen.
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,284 @@
+//! Client-side rate limiting and coalescing of repeated messages.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::error::{GotifyError, Result};
+use crate::message::NewMessage;
+use crate::priority::Priority;
+use crate::response_types::Message;
+use crate::sync::{lock, ErrorHandler};
+use crate::AsyncGotify;
+
+/// A token bucket: up to `burst` messages at once, refilled at `burst`
+/// messages per `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub burst: u32,
+    pub per: Duration,
+}
+
+impl RateLimit {
+    pub fn new(burst: u32, per: Duration) -> Self {
+        Self { burst, per }
+    }
+}
+
+/// What `Throttle` limits. Both are off by default.
+#[derive(Debug, Clone, Default)]
+pub struct ThrottlePolicy {
+    /// Applied separately to each app token.
+    pub rate_limit: Option<RateLimit>,
+    /// How long identical messages are folded together after the first one
+    /// is sent. Messages are identical when their app token, title and body
+    /// match.
+    pub dedup_window: Option<Duration>,
+}
+
+/// What `Throttle` did with a message.
+#[derive(Debug)]
+pub enum Throttled {
+    Sent(Message),
+    /// An identical message was sent recently; this one is counted towards
+    /// the "repeated N times" message sent when the dedup window closes.
+    Coalesced,
+    /// The app token's rate limit is exhausted, so the message was dropped.
+    RateLimited,
+}
+
+/// Guards `create_message` against floods of notifications.
+///
+/// One `Throttle` can front any number of clients; rate limits and dedup
+/// windows are kept per app token. When a dedup window closes with repeats
+/// folded into it, a copy of the first message with a " (repeated N times)"
+/// suffix is sent from a background task, so a `Throttle` must be used within
+/// a tokio runtime. Failures to send those are passed to `on_error`.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use gotify_rs::*;
+/// # async fn run(client: AsyncGotify) -> Result<()> {
+/// let throttle = Throttle::new(ThrottlePolicy {
+///     rate_limit: Some(RateLimit::new(10, Duration::from_secs(60))),
+///     dedup_window: Some(Duration::from_secs(300)),
+/// });
+/// throttle
+///     .create_message(&client, "disk full".to_string(), None, None)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct Throttle {
+    policy: ThrottlePolicy,
+    state: Arc<Mutex<State>>,
+    on_error: Option<ErrorHandler>,
+}
+
+#[derive(Default)]
+struct State {
+    buckets: HashMap<Option<String>, Bucket>,
+    windows: HashMap<Key, Window>,
+    next_window: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    app_token: Option<String>,
+    title: Option<String>,
+    message: String,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl Bucket {
+    fn take(&mut self, limit: RateLimit) -> bool {
+        let now = Instant::now();
+        let burst = f64::from(limit.burst);
+        let refill = now.duration_since(self.refilled).as_secs_f64() / limit.per.as_secs_f64();
+        self.tokens = (self.tokens + refill * burst).min(burst);
+        self.refilled = now;
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    /// Returns a token taken for a message that couldn't be sent.
+    fn give_back(&mut self, limit: RateLimit) {
+        self.tokens = (self.tokens + 1.0).min(f64::from(limit.burst));
+    }
+}
+
+struct Window {
+    id: u64,
+    client: AsyncGotify,
+    first: NewMessage,
+    repeats: u32,
+}
+
+impl Window {
+    /// The message summarising the repeats, if there were any.
+    fn into_summary(self) -> Option<(AsyncGotify, NewMessage)> {
+        if self.repeats == 0 {
+            return None;
+        }
+        let mut summary = self.first;
+        let times = if self.repeats == 1 { "time" } else { "times" };
+        summary.message = format!("{} (repeated {} {times})", summary.message, self.repeats);
+        Some((self.client, summary))
+    }
+}
+
+impl Throttle {
+    pub fn new(policy: ThrottlePolicy) -> Self {
+        Self {
+            policy,
+            state: Default::default(),
+            on_error: None,
+        }
+    }
+
+    /// Called with each "repeated N times" message the server didn't accept.
+    pub fn on_error(
+        mut self,
+        on_error: impl Fn(NewMessage, GotifyError) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_error = Some(Arc::new(on_error));
+        self
+    }
+
+    pub async fn create_message(
+        &self,
+        client: &AsyncGotify,
+        message: String,
+        priority: Option<Priority>,
+        title: Option<String>,
+    ) -> Result<Throttled> {
+        self.send_message(
+            client,
+            NewMessage {
+                message,
+                title,
+                priority,
+                extras: Default::default(),
+            },
+        )
+        .await
+    }
+
+    pub async fn send_message(
+        &self,
+        client: &AsyncGotify,
+        message: impl Into<NewMessage>,
+    ) -> Result<Throttled> {
+        let message = message.into();
+        let key = Key {
+            app_token: client.app_token().map(str::to_string),
+            title: message.title.clone(),
+            message: message.message.clone(),
+        };
+        let window_id = {
+            let mut state = self.state();
+            if let Some(window) = state.windows.get_mut(&key) {
+                window.repeats += 1;
+                return Ok(Throttled::Coalesced);
+            }
+            if let Some(limit) = self.policy.rate_limit {
+                let bucket = state
+                    .buckets
+                    .entry(key.app_token.clone())
+                    .or_insert_with(|| Bucket {
+                        tokens: f64::from(limit.burst),
+                        refilled: Instant::now(),
+                    });
+                if !bucket.take(limit) {
+                    return Ok(Throttled::RateLimited);
+                }
+            }
+            // Open the window before sending so that repeats arriving while
+            // the first message is in flight are folded into it.
+            self.policy.dedup_window.map(|window| {
+                state.next_window += 1;
+                let id = state.next_window;
+                state.windows.insert(
+                    key.clone(),
+                    Window {
+                        id,
+                        client: client.clone(),
+                        first: message.clone(),
+                        repeats: 0,
+                    },
+                );
+                tokio::spawn(self.clone().close_after(key.clone(), id, window));
+                id
+            })
+        };
+        match client.send_message(message).await {
+            Ok(sent) => Ok(Throttled::Sent(sent)),
+            Err(e) => {
+                // The message never arrived, so a retry must go out rather
+                // than be folded into a window for it.
+                let mut state = self.state();
+                if matches!(state.windows.get(&key), Some(open) if Some(open.id) == window_id) {
+                    state.windows.remove(&key);
+                }
+                if let Some(limit) = self.policy.rate_limit {
+                    if let Some(bucket) = state.buckets.get_mut(&key.app_token) {
+                        bucket.give_back(limit);
+                    }
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Sends the "repeated N times" message of every open dedup window now,
+    /// rather than when the windows close, returning how many were sent.
+    pub async fn flush(&self) -> usize {
+        let windows: Vec<_> = self.state().windows.drain().map(|(_, w)| w).collect();
+        let mut sent = 0;
+        for window in windows {
+            if let Some((client, summary)) = window.into_summary() {
+                sent += usize::from(self.send_summary(&client, summary).await);
+            }
+        }
+        sent
+    }
+
+    async fn close_after(self, key: Key, id: u64, window: Duration) {
+        tokio::time::sleep(window).await;
+        let closed = {
+            let mut state = self.state();
+            // The window may have been flushed and a new one opened since.
+            match state.windows.get(&key) {
+                Some(open) if open.id == id => state.windows.remove(&key),
+                _ => None,
+            }
+        };
+        if let Some((client, summary)) = closed.and_then(Window::into_summary) {
+            self.send_summary(&client, summary).await;
+        }
+    }
+
+    async fn send_summary(&self, client: &AsyncGotify, summary: NewMessage) -> bool {
+        match client.send_message(summary.clone()).await {
+            Ok(_) => true,
+            Err(e) => {
+                if let Some(on_error) = &self.on_error {
+                    on_error(summary, e);
+                }
+                false
+            }
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
@@ -0,0 +1,123 @@
+//! Message and token timestamps.
+//!
+//! Gotify sends RFC 3339 strings. With the `chrono` or `time` feature they
+//! are parsed into that crate's datetime type, and messages can be filtered
+//! by date; without either they stay strings.
+
+/// A timestamp as sent by the server: `chrono::DateTime<FixedOffset>` with
+/// the `chrono` feature, `time::OffsetDateTime` with only the `time` feature,
+/// and the RFC 3339 `String` otherwise. When both features are enabled,
+/// `chrono` wins.
+///
+/// With either feature, a timestamp that isn't valid RFC 3339 fails to
+/// decode along with the rest of the response.
+#[cfg(feature = "chrono")]
+pub type Timestamp = chrono::DateTime<chrono::FixedOffset>;
+#[cfg(all(feature = "time", not(feature = "chrono")))]
+pub type Timestamp = time::OffsetDateTime;
+#[cfg(not(any(feature = "chrono", feature = "time")))]
+pub type Timestamp = String;
+
+/// `#[serde(with)]` helpers that read and write a `Timestamp` as RFC 3339,
+/// whichever type it is.
+pub(crate) mod rfc3339 {
+    #[cfg(all(feature = "time", not(feature = "chrono")))]
+    pub(crate) use time::serde::rfc3339::{deserialize, option, serialize};
+
+    #[cfg(not(all(feature = "time", not(feature = "chrono"))))]
+    pub(crate) use self::native::{deserialize, option, serialize};
+
+    /// `chrono`'s types and `String` already (de)serialize as RFC 3339.
+    #[cfg(not(all(feature = "time", not(feature = "chrono"))))]
+    mod native {
+        use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+        use super::super::Timestamp;
+
+        pub(crate) fn serialize<S: Serializer>(
+            date: &Timestamp,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            date.serialize(serializer)
+        }
+
+        pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Timestamp, D::Error> {
+            Timestamp::deserialize(deserializer)
+        }
+
+        pub(crate) mod option {
+            use super::*;
+
+            pub(crate) fn serialize<S: Serializer>(
+                date: &Option<Timestamp>,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                date.serialize(serializer)
+            }
+
+            pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Option<Timestamp>, D::Error> {
+                Option::<Timestamp>::deserialize(deserializer)
+            }
+        }
+    }
+}
+
+/// Nanoseconds since the Unix epoch, for comparing against a `DateBound`.
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub(crate) fn unix_nanos(date: &Timestamp) -> i128 {
+    bounds::DateBound::unix_nanos(date)
+}
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+pub use self::bounds::DateBound;
+
+#[cfg(any(feature = "chrono", feature = "time"))]
+mod bounds {
+    use std::ops::{Bound, RangeBounds};
+
+    use crate::response_types::Message;
+
+    /// A point in time that messages can be filtered by: a `chrono::DateTime`
+    /// or a `time::OffsetDateTime`, depending on the enabled features.
+    pub trait DateBound {
+        /// Nanoseconds since the Unix epoch.
+        fn unix_nanos(&self) -> i128;
+    }
+
+    #[cfg(feature = "chrono")]
+    impl<Tz: chrono::TimeZone> DateBound for chrono::DateTime<Tz> {
+        fn unix_nanos(&self) -> i128 {
+            // Out of range only after the year 2262.
+            self.timestamp_nanos_opt().map_or(i128::MAX, i128::from)
+        }
+    }
+
+    #[cfg(feature = "time")]
+    impl DateBound for time::OffsetDateTime {
+        fn unix_nanos(&self) -> i128 {
+            self.unix_timestamp_nanos()
+        }
+    }
+
+    impl Message {
+        /// Whether the message was sent within `range`, e.g. `start..end` or `since..`.
+        pub fn sent_within<B: DateBound>(&self, range: impl RangeBounds<B>) -> bool {
+            let date = super::unix_nanos(&self.date);
+            let after_start = match range.start_bound() {
+                Bound::Included(start) => date >= start.unix_nanos(),
+                Bound::Excluded(start) => date > start.unix_nanos(),
+                Bound::Unbounded => true,
+            };
+            let before_end = match range.end_bound() {
+                Bound::Included(end) => date <= end.unix_nanos(),
+                Bound::Excluded(end) => date < end.unix_nanos(),
+                Bound::Unbounded => true,
+            };
+            after_start && before_end
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Reading and writing the client token that `login_cached` keeps between runs.
+
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+
+fn parse(contents: &str) -> Option<String> {
+    let token = contents.trim();
+    (!token.is_empty()).then(|| token.to_string())
+}
+
+pub(crate) fn read(path: &Path) -> Result<Option<String>> {
+    match std::fs::read_to_string(path) {
+        Ok(contents) => Ok(parse(&contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// A fresh file next to `path` for the new token, so that permissions never
+/// depend on whatever file was there before.
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    tmp.into()
+}
+
+/// Replaces `path` with what `contents` writes, readable only by the current
+/// user on Unix. The file is replaced rather than rewritten in place, so an
+/// existing file with looser permissions doesn't end up holding secrets.
+pub(crate) fn replace_private(
+    path: &Path,
+    contents: impl FnOnce(&mut File) -> Result<()>,
+) -> Result<()> {
+    let tmp = tmp_path(path);
+    match std::fs::remove_file(&tmp) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp)?;
+    contents(&mut file)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Writes the token through `replace_private`.
+pub(crate) fn write(path: &Path, token: &str) -> Result<()> {
+    replace_private(path, |file| Ok(writeln!(file, "{token}")?))
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn read_async(path: &Path) -> Result<Option<String>> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(contents) => Ok(parse(&contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(feature = "async")]
+pub(crate) async fn write_async(path: &Path, token: &str) -> Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let tmp = tmp_path(path);
+    match tokio::fs::remove_file(&tmp).await {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(&tmp).await?;
+    file.write_all(format!("{token}\n").as_bytes()).await?;
+    file.sync_all().await?;
+    tokio::fs::rename(&tmp, path).await?;
+    Ok(())
+}